@@ -0,0 +1,135 @@
+use std::{
+    fmt,
+    ops::{BitXor, BitXorAssign},
+};
+
+/// A fixed number of bits packed in `u64` words, bit `i` being bit `i % 64`
+/// of word `i / 64`. Bits past `len` are always zero.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct BitSet {
+    len: usize,
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    /// `len` bits, all zero.
+    pub fn new(len: usize) -> Self {
+        BitSet {
+            len,
+            words: vec![0; len.div_ceil(64)],
+        }
+    }
+
+    /// `len` bits, with the ones at `indices` set.
+    pub fn from_indices(len: usize, indices: impl IntoIterator<Item = usize>) -> Self {
+        let mut set = BitSet::new(len);
+        for i in indices {
+            set.set(i, true);
+        }
+        set
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, i: usize) -> bool {
+        assert!(i < self.len, "bit {i} out of {}", self.len);
+        self.words[i / 64] >> (i % 64) & 1 == 1
+    }
+
+    pub fn set(&mut self, i: usize, value: bool) {
+        if self.get(i) != value {
+            self.flip(i);
+        }
+    }
+
+    pub fn flip(&mut self, i: usize) {
+        assert!(i < self.len, "bit {i} out of {}", self.len);
+        self.words[i / 64] ^= 1 << (i % 64);
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn none(&self) -> bool {
+        self.words.iter().all(|&word| word == 0)
+    }
+
+    /// Indices of the set bits, in increasing order.
+    pub fn ones(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(w, &word)| {
+            let mut word = word;
+            std::iter::from_fn(move || {
+                (word != 0).then(|| {
+                    let bit = word.trailing_zeros() as usize;
+                    word &= word - 1;
+                    w * 64 + bit
+                })
+            })
+        })
+    }
+}
+
+impl BitXorAssign<&BitSet> for BitSet {
+    fn bitxor_assign(&mut self, other: &BitSet) {
+        assert_eq!(self.len, other.len);
+        for (word, other) in self.words.iter_mut().zip(&other.words) {
+            *word ^= other;
+        }
+    }
+}
+
+impl BitXor<&BitSet> for &BitSet {
+    type Output = BitSet;
+
+    fn bitxor(self, other: &BitSet) -> BitSet {
+        let mut result = self.clone();
+        result ^= other;
+        result
+    }
+}
+
+/// `#` for ones and `.` for zeros, like the lights of a day 10 machine.
+impl fmt::Display for BitSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for i in 0..self.len {
+            write!(f, "{}", if self.get(i) { '#' } else { '.' })?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_bit_set() {
+    let mut set = BitSet::new(130);
+    assert_eq!(set.len(), 130);
+    assert!(set.none());
+    set.set(0, true);
+    set.set(64, true);
+    set.flip(129);
+    set.flip(3);
+    set.flip(3);
+    assert!(set.get(0) && set.get(64) && set.get(129) && !set.get(3));
+    assert_eq!(set.count_ones(), 3);
+    assert_eq!(set.ones().collect::<Vec<_>>(), [0, 64, 129]);
+
+    let other = BitSet::from_indices(130, [0, 1, 129]);
+    let both = &set ^ &other;
+    assert_eq!(both.ones().collect::<Vec<_>>(), [1, 64]);
+    set ^= &other;
+    assert_eq!(set, both);
+    set ^= &both;
+    assert!(set.none());
+
+    assert_eq!(BitSet::from_indices(4, [1, 2]).to_string(), ".##.");
+    assert!(BitSet::default().is_empty());
+}
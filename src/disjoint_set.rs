@@ -0,0 +1,161 @@
+use std::{collections::HashMap, hash::Hash};
+
+/// Union-find over arbitrary elements, with path compression and union by
+/// size.
+#[derive(Debug, Clone)]
+pub struct DisjointSet<T> {
+    indices: HashMap<T, usize>,
+    parents: Vec<usize>,
+    /// Size of the component, only meaningful for roots.
+    sizes: Vec<usize>,
+    num_components: usize,
+}
+
+impl<T: Hash + Eq> Default for DisjointSet<T> {
+    fn default() -> Self {
+        DisjointSet {
+            indices: HashMap::new(),
+            parents: vec![],
+            sizes: vec![],
+            num_components: 0,
+        }
+    }
+}
+
+impl<T: Hash + Eq> FromIterator<T> for DisjointSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut set = DisjointSet::default();
+        for element in iter {
+            set.insert(element);
+        }
+        set
+    }
+}
+
+impl<T: Hash + Eq> DisjointSet<T> {
+    pub fn new() -> Self {
+        DisjointSet::default()
+    }
+
+    /// Adds `element` in a component of its own. Returns `false` if it was
+    /// already there.
+    pub fn insert(&mut self, element: T) -> bool {
+        if self.indices.contains_key(&element) {
+            return false;
+        }
+        self.indices.insert(element, self.parents.len());
+        self.parents.push(self.parents.len());
+        self.sizes.push(1);
+        self.num_components += 1;
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.parents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parents.is_empty()
+    }
+
+    pub fn contains(&self, element: &T) -> bool {
+        self.indices.contains_key(element)
+    }
+
+    fn root(&mut self, mut i: usize) -> usize {
+        let mut root = i;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+        // point everything on the way directly at the root
+        while self.parents[i] != root {
+            let next = self.parents[i];
+            self.parents[i] = root;
+            i = next;
+        }
+        root
+    }
+
+    /// An identifier of the component `element` is in, shared by all elements
+    /// of that component until the next union.
+    ///
+    /// Panics if `element` was never inserted.
+    pub fn find(&mut self, element: &T) -> usize {
+        let i = self.indices[element];
+        self.root(i)
+    }
+
+    /// Merges the components of `a` and `b`. Returns `false` if they were
+    /// already the same.
+    ///
+    /// Panics if either element was never inserted.
+    pub fn union(&mut self, a: &T, b: &T) -> bool {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+        if self.sizes[a] < self.sizes[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.parents[b] = a;
+        self.sizes[a] += self.sizes[b];
+        self.num_components -= 1;
+        true
+    }
+
+    pub fn same_component(&mut self, a: &T, b: &T) -> bool {
+        self.find(a) == self.find(b)
+    }
+
+    pub fn component_count(&self) -> usize {
+        self.num_components
+    }
+
+    pub fn component_size(&mut self, element: &T) -> usize {
+        let root = self.find(element);
+        self.sizes[root]
+    }
+
+    /// Sizes of all components, largest first.
+    pub fn component_sizes(&self) -> Vec<usize> {
+        let mut sizes: Vec<usize> = (0..self.parents.len())
+            .filter(|&i| self.parents[i] == i)
+            .map(|i| self.sizes[i])
+            .collect();
+        sizes.sort_by(|a, b| b.cmp(a));
+        sizes
+    }
+}
+
+#[test]
+fn test_disjoint_set() {
+    let mut set: DisjointSet<char> = "abcdef".chars().collect();
+    assert_eq!(set.len(), 6);
+    assert_eq!(set.component_count(), 6);
+    assert!(!set.insert('a'));
+
+    assert!(set.union(&'a', &'b'));
+    assert!(set.union(&'c', &'d'));
+    assert!(set.union(&'b', &'d'));
+    assert!(!set.union(&'a', &'c'));
+    assert!(set.same_component(&'a', &'d'));
+    assert!(!set.same_component(&'a', &'e'));
+    assert_eq!(set.component_count(), 3);
+    assert_eq!(set.component_size(&'c'), 4);
+    assert_eq!(set.component_sizes(), vec![4, 1, 1]);
+
+    assert!(set.insert('g'));
+    assert!(set.union(&'e', &'g'));
+    assert_eq!(set.component_sizes(), vec![4, 2, 1]);
+}
+
+#[test]
+fn test_disjoint_set_long_chain() {
+    // unions that would build a very deep tree without union by size
+    let mut set: DisjointSet<u32> = (0..100_000).collect();
+    for i in 1..100_000 {
+        set.union(&i, &(i - 1));
+    }
+    assert_eq!(set.component_count(), 1);
+    assert_eq!(set.component_size(&0), 100_000);
+}
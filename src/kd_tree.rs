@@ -0,0 +1,220 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use crate::{BoxPair, Metric, Position};
+
+fn coordinate(position: &Position, axis: usize) -> i64 {
+    match axis {
+        0 => position.0,
+        1 => position.1,
+        _ => position.2,
+    }
+}
+
+/// A k-d tree over day 8 junction boxes, for finding close pairs without
+/// looking at all of them. Metrics without an axis bound can't skip any part
+/// of the tree, so every search looks at all boxes.
+#[derive(Debug, Clone)]
+pub struct KdTree<'a, M: ?Sized> {
+    positions: &'a [Position],
+    metric: &'a M,
+    /// Indices into `positions`. Each range `lo..hi` of the tree has its
+    /// splitting box in the middle, with the boxes before it on the low side
+    /// of the splitting plane and the ones after it on the high side.
+    order: Vec<usize>,
+}
+
+impl<'a, M: Metric + ?Sized> KdTree<'a, M> {
+    pub fn new(positions: &'a [Position], metric: &'a M) -> KdTree<'a, M> {
+        fn build(order: &mut [usize], positions: &[Position], depth: usize) {
+            if order.len() <= 1 {
+                return;
+            }
+            let mid = order.len() / 2;
+            let axis = depth % 3;
+            order.select_nth_unstable_by_key(mid, |&i| coordinate(&positions[i], axis));
+            let (low, high) = order.split_at_mut(mid);
+            build(low, positions, depth + 1);
+            build(&mut high[1..], positions, depth + 1);
+        }
+
+        let mut order: Vec<usize> = (0..positions.len()).collect();
+        build(&mut order, positions, 0);
+        KdTree {
+            positions,
+            metric,
+            order,
+        }
+    }
+
+    /// The `k` boxes closest to box `index`, excluding itself, as (distance,
+    /// index) sorted by distance then index.
+    pub fn nearest(&self, index: usize, k: usize) -> Vec<(i64, usize)> {
+        fn search<M: Metric + ?Sized>(
+            tree: &KdTree<M>,
+            lo: usize,
+            hi: usize,
+            depth: usize,
+            index: usize,
+            k: usize,
+            best: &mut BinaryHeap<(i64, usize)>,
+        ) {
+            if lo >= hi {
+                return;
+            }
+            let mid = lo + (hi - lo) / 2;
+            let candidate = tree.order[mid];
+            let target = &tree.positions[index];
+            if candidate != index {
+                let entry = (
+                    tree.metric.distance(target, &tree.positions[candidate]),
+                    candidate,
+                );
+                if best.len() < k {
+                    best.push(entry);
+                } else if entry < *best.peek().unwrap() {
+                    best.pop();
+                    best.push(entry);
+                }
+            }
+
+            let axis = depth % 3;
+            let offset = coordinate(target, axis) - coordinate(&tree.positions[candidate], axis);
+            let (near, far) = if offset < 0 {
+                ((lo, mid), (mid + 1, hi))
+            } else {
+                ((mid + 1, hi), (lo, mid))
+            };
+            search(tree, near.0, near.1, depth + 1, index, k, best);
+            // boxes exactly as far as the worst one so far can still win on
+            // their index
+            let worth_it = match tree.metric.axis_bound(offset) {
+                Some(bound) => best.len() < k || bound <= best.peek().unwrap().0,
+                None => true,
+            };
+            if worth_it {
+                search(tree, far.0, far.1, depth + 1, index, k, best);
+            }
+        }
+
+        let mut best = BinaryHeap::with_capacity(k + 1);
+        if k > 0 {
+            search(self, 0, self.order.len(), 0, index, k, &mut best);
+        }
+        best.into_sorted_vec()
+    }
+
+    /// Iterates over every pair of boxes once, in the same order as
+    /// `sorted_pairs`, only looking as far from each box as needed.
+    pub fn pairs(&self) -> NearestPairs<'_, 'a, M> {
+        let mut pairs = NearestPairs {
+            tree: self,
+            neighbours: vec![vec![]; self.positions.len()],
+            cursors: vec![0; self.positions.len()],
+            next: BinaryHeap::new(),
+        };
+        for i in 0..self.positions.len() {
+            if let Some(pair) = pairs.advance(i) {
+                pairs.next.push(Reverse(pair));
+            }
+        }
+        pairs
+    }
+}
+
+/// Pairs of boxes closest first, see `KdTree::pairs`.
+///
+/// Each box has a stream of its neighbours with a higher index, closest
+/// first, fetched from the tree in growing batches. Merging the streams gives
+/// all pairs in order.
+pub struct NearestPairs<'t, 'a, M: ?Sized> {
+    tree: &'t KdTree<'a, M>,
+    neighbours: Vec<Vec<(i64, usize)>>,
+    cursors: Vec<usize>,
+    /// The next pair of each box whose stream isn't exhausted.
+    next: BinaryHeap<Reverse<BoxPair>>,
+}
+
+impl<M: Metric + ?Sized> NearestPairs<'_, '_, M> {
+    /// Next pair in the stream of box `i`.
+    fn advance(&mut self, i: usize) -> Option<BoxPair> {
+        let num_boxes = self.tree.positions.len();
+        loop {
+            if self.cursors[i] == self.neighbours[i].len() {
+                let fetched = self.neighbours[i].len();
+                if fetched + 1 >= num_boxes {
+                    return None;
+                }
+                // the closest k boxes start with the closest k / 2, so only
+                // the new ones matter
+                self.neighbours[i] = self.tree.nearest(i, (2 * fetched).max(8));
+            }
+            let (distance, j) = self.neighbours[i][self.cursors[i]];
+            self.cursors[i] += 1;
+            if j > i {
+                return Some(BoxPair {
+                    distance,
+                    left: i,
+                    right: j,
+                });
+            }
+        }
+    }
+}
+
+impl<M: Metric + ?Sized> Iterator for NearestPairs<'_, '_, M> {
+    type Item = BoxPair;
+
+    fn next(&mut self) -> Option<BoxPair> {
+        let Reverse(pair) = self.next.pop()?;
+        if let Some(following) = self.advance(pair.left) {
+            self.next.push(Reverse(following));
+        }
+        Some(pair)
+    }
+}
+
+#[test]
+fn test_kd_tree_pairs() {
+    use crate::{Chebyshev, Manhattan, SquaredEuclidean, sorted_pairs};
+
+    // pseudo random boxes on a small grid, so that many pairs are at the same
+    // distance
+    let mut seed = 12345u64;
+    let mut random = move || {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        ((seed >> 33) % 20) as i64 - 10
+    };
+    let positions: Vec<Position> = (0..300).map(|_| (random(), random(), random())).collect();
+
+    let tree = KdTree::new(&positions, &SquaredEuclidean);
+    let expected = sorted_pairs(&positions, &SquaredEuclidean);
+    assert_eq!(
+        tree.pairs().take(1000).collect::<Vec<_>>(),
+        expected[..1000]
+    );
+    assert_eq!(tree.pairs().collect::<Vec<_>>(), expected);
+
+    let mut brute_force: Vec<(i64, usize)> = (0..positions.len())
+        .filter(|&j| j != 7)
+        .map(|j| (SquaredEuclidean.distance(&positions[7], &positions[j]), j))
+        .collect();
+    brute_force.sort();
+    assert_eq!(tree.nearest(7, 25), brute_force[..25]);
+
+    let heights = |a: &Position, b: &Position| (a.2 - b.2).abs();
+    let metrics: [&dyn Metric; 3] = [&Manhattan, &Chebyshev, &heights];
+    for metric in metrics {
+        assert_eq!(
+            KdTree::new(&positions, metric)
+                .pairs()
+                .take(2000)
+                .collect::<Vec<_>>(),
+            sorted_pairs(&positions, metric)[..2000]
+        );
+    }
+
+    assert_eq!(KdTree::new(&[], &Manhattan).pairs().count(), 0);
+    assert_eq!(KdTree::new(&[(1, 2, 3)], &Manhattan).pairs().count(), 0);
+}
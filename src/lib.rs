@@ -1,10 +1,21 @@
 use std::{
+    cmp::Reverse,
     collections::{BTreeMap, HashMap, HashSet, VecDeque},
     hash::Hash,
     iter::Enumerate,
     ops::DerefMut,
 };
 
+pub mod bit_set;
+pub mod disjoint_set;
+pub mod kd_tree;
+pub mod polygon;
+
+use bit_set::BitSet;
+use disjoint_set::DisjointSet;
+use kd_tree::KdTree;
+use polygon::{ContainmentIndex, Point, RectilinearPolygon};
+
 /// Advent of Code 2025 in rust 🦀 :)
 
 pub fn d1_1(input: &str) -> u64 {
@@ -444,967 +455,2999 @@ fn test_d5_2() {
     println!("d5_2={}", d5_2(include_str!("day5.txt")));
 }
 
-pub fn d6_1(input: &str) -> u64 {
-    // replace intervals between elements by a single space, to make splitting
-    // easier.
-    let cleaned_input: Vec<_> = input
-        .lines()
-        .map(|line| line.split_ascii_whitespace().collect::<Vec<_>>())
-        .collect();
+/// Operators that can appear on the last line of a day 6 worksheet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Min,
+    Max,
+    Power,
+}
 
-    let num_operands = cleaned_input.len() - 1;
-    let operand_lines = &cleaned_input[..num_operands];
-    let operators = &cleaned_input[num_operands];
+impl Operator {
+    /// Parses an operator token from the worksheet: `+`, `-`, `*`, `/`, `min`,
+    /// `max` or `^`.
+    pub fn parse(token: &str) -> Option<Operator> {
+        match token {
+            "+" => Some(Operator::Add),
+            "-" => Some(Operator::Subtract),
+            "*" => Some(Operator::Multiply),
+            "/" => Some(Operator::Divide),
+            "min" => Some(Operator::Min),
+            "max" => Some(Operator::Max),
+            "^" => Some(Operator::Power),
+            _ => None,
+        }
+    }
 
-    for op_list in operand_lines {
-        assert_eq!(op_list.len(), operators.len());
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Operator::Add => "+",
+            Operator::Subtract => "-",
+            Operator::Multiply => "*",
+            Operator::Divide => "/",
+            Operator::Min => "min",
+            Operator::Max => "max",
+            Operator::Power => "^",
+        }
     }
 
-    let result = (0..operators.len()).fold(0, |sum, i| {
-        let operator = operators[i];
-        let operands = operand_lines
-            .iter()
-            .map(|op_line| op_line[i].parse::<u64>().unwrap())
-            .collect::<Vec<_>>();
+    /// Folds the operands from left to right, so `[a, b, c]` with `Subtract`
+    /// is `(a - b) - c` and with `Power` is `(a ^ b) ^ c`.
+    pub fn apply(&self, operands: &[u64]) -> Result<u64, EvalError> {
+        let (first, rest) = operands.split_first().ok_or(EvalError::NoOperands)?;
+        rest.iter().try_fold(*first, |acc, &x| match self {
+            Operator::Add => acc.checked_add(x).ok_or(EvalError::Overflow),
+            Operator::Subtract => acc.checked_sub(x).ok_or(EvalError::Overflow),
+            Operator::Multiply => acc.checked_mul(x).ok_or(EvalError::Overflow),
+            Operator::Divide => acc.checked_div(x).ok_or(EvalError::DivisionByZero),
+            Operator::Min => Ok(acc.min(x)),
+            Operator::Max => Ok(acc.max(x)),
+            // 0 and 1 stay put whatever the exponent, even past u32::MAX
+            Operator::Power if acc <= 1 => Ok(if x == 0 { 1 } else { acc }),
+            Operator::Power => u32::try_from(x)
+                .ok()
+                .and_then(|exponent| acc.checked_pow(exponent))
+                .ok_or(EvalError::Overflow),
+        })
+    }
+}
 
-        let r = match operator {
-            "+" => operands
-                .iter()
-                .fold(0, |sum, operand_str| sum + operand_str),
-            "*" => operands
-                .iter()
-                .fold(1, |product, operand_str| product * operand_str),
-            _ => panic!(),
-        };
-        return sum + r;
-    });
+impl std::fmt::Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.symbol())
+    }
+}
 
-    result
+/// Why a single worksheet problem could not be evaluated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvalError {
+    NoOperands,
+    /// The result does not fit in a `u64`, including negative results of a
+    /// subtraction.
+    Overflow,
+    DivisionByZero,
 }
 
-#[test]
-fn test_d6_1() {
-    println!("d6_1={}", d6_1(include_str!("day6.txt")));
+impl std::fmt::Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            EvalError::NoOperands => "no operands",
+            EvalError::Overflow => "overflow",
+            EvalError::DivisionByZero => "division by zero",
+        })
+    }
 }
 
-pub fn d6_2(input: &str) -> u64 {
-    // Parsing the input just got harder.
+/// One column of the worksheet: an operator and the operands it applies to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub operator: Operator,
+    pub operands: Vec<u64>,
+}
 
-    // parse the numbers from right to left in column-wise fashion
-    let num_operands = input.lines().count() - 1;
-    let num_columns = input.lines().nth(0).unwrap().chars().count();
+impl Problem {
+    pub fn evaluate(&self) -> Result<u64, EvalError> {
+        self.operator.apply(&self.operands)
+    }
+}
 
-    // turn the input into columns
-    let columns = (0..num_columns).rev().map(|i| {
-        input
-            .lines()
-            .take(num_operands)
-            .map(move |line| line.chars().nth(i).unwrap())
-            .collect::<Vec<_>>()
-    });
+/// The value of every problem of a worksheet, in worksheet order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Evaluation {
+    pub values: Vec<Result<u64, EvalError>>,
+}
 
-    let operand_sets = columns.fold(vec![vec![]], |mut operand_vec: Vec<Vec<u64>>, column| {
-        if column.iter().all(|&c| c == ' ') {
-            // Empty column -> move to next computation
-            operand_vec.push(vec![]);
-        } else {
-            let operand = column.iter().fold(0, |sum, &c| {
-                if c == ' ' {
-                    sum
-                } else {
-                    sum * 10 + (c.to_digit(10).unwrap() as u64)
-                }
-            });
-            operand_vec.last_mut().unwrap().push(operand);
-        }
+impl Evaluation {
+    /// Grand total of all problems, or `None` if any problem failed or the sum
+    /// itself overflows.
+    pub fn total(&self) -> Option<u64> {
+        self.values
+            .iter()
+            .try_fold(0u64, |sum, value| sum.checked_add(*value.as_ref().ok()?))
+    }
 
-        return operand_vec;
-    });
+    /// The problems that could not be evaluated, with their index.
+    pub fn errors(&self) -> impl Iterator<Item = (usize, EvalError)> + '_ {
+        self.values
+            .iter()
+            .enumerate()
+            .filter_map(|(i, value)| value.err().map(|e| (i, e)))
+    }
+}
 
-    // Operators are still ~easy if we use split_ascii_whitespace()
-    #[derive(Debug)]
-    enum Operator {
-        Add,
-        Multiply,
+pub fn evaluate_problems(problems: &[Problem]) -> Evaluation {
+    Evaluation {
+        values: problems.iter().map(Problem::evaluate).collect(),
     }
-    let operators: Vec<Operator> = input
-        .lines()
-        .last()
-        .unwrap()
-        .split_ascii_whitespace()
-        .rev()
-        .map(|op_str| match op_str {
-            "+" => Operator::Add,
-            "*" => Operator::Multiply,
-            _ => panic!(),
-        })
-        .collect();
+}
 
-    return operators
-        .iter()
-        .zip(operand_sets)
-        .fold(0, |sum, (operator, operands)| {
-            // dbg!(&operator, &operands);
-            let delta = match operator {
-                Operator::Add => operands.iter().fold(0, |sum, e| sum + e),
-                Operator::Multiply => operands.iter().fold(1, |product, e| product * e),
-            };
-            // dbg!(&delta);
-            sum + delta
-        });
+/// Why a day 6 worksheet could not be read. Lines and columns are 0-based and
+/// columns count characters after tab expansion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WorksheetError {
+    /// The worksheet needs at least one operand line and the operator line.
+    TooFewLines,
+    InvalidCharacter {
+        line: usize,
+        column: usize,
+        found: char,
+    },
+    UnknownOperator {
+        column: usize,
+        token: String,
+    },
+    /// An operator starts in a column where no problem has any digit.
+    OperatorOutsideProblem {
+        column: usize,
+    },
+    /// An operator overlaps two problems, or a problem has several operators.
+    AmbiguousOperator {
+        column: usize,
+    },
+    MissingOperator {
+        column: usize,
+    },
+    /// A line has no digits within the columns of a problem.
+    MissingOperand {
+        line: usize,
+        column: usize,
+    },
+    /// A line has several numbers within the columns of a single problem, so
+    /// the row-wise reading cannot tell which one belongs to it.
+    AmbiguousOperand {
+        line: usize,
+        column: usize,
+    },
+    OperandOverflow {
+        column: usize,
+    },
 }
 
-#[test]
-fn test_d6_2() {
-    println!("d6_2={}", d6_2(include_str!("day6.txt")));
+/// A day 6 worksheet with its problems located by column.
+///
+/// Lines may have different lengths (e.g. when an editor strips trailing
+/// spaces), use CRLF endings or contain tabs; shorter lines are padded with
+/// spaces and tabs are expanded to the next tab stop. Blank lines are ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Worksheet {
+    /// Operand lines with their line number in the input, padded to the same
+    /// width.
+    lines: Vec<(usize, Vec<char>)>,
+    /// Column span and operator of each problem, left to right.
+    problems: Vec<(std::ops::Range<usize>, Operator)>,
 }
 
-pub fn d7_1(input: &str) -> usize {
-    // the question is how many splitters the beam hits.
-    // brute force -> simulate every split and propagate each beam, eliminating
-    // double counts at every vertical step.
-
-    fn beam_step(beams_in: &HashSet<usize>, line: &str) -> (usize, HashSet<usize>) {
-        let mut beams_out = HashSet::new();
-        let mut num_splits = 0;
-
-        line.chars().enumerate().for_each(|(i, c)| {
-            if beams_in.contains(&i) {
-                match c {
-                    '.' => {
-                        beams_out.insert(i);
-                    }
-                    '^' => {
-                        num_splits += 1;
-                        if i > 0 {
-                            beams_out.insert(i - 1);
-                        }
-                        if i < line.chars().count() - 1 {
-                            beams_out.insert(i + 1);
-                        }
-                    }
-                    _ => panic!(),
+impl Worksheet {
+    pub fn parse(input: &str) -> Result<Worksheet, WorksheetError> {
+        Worksheet::parse_with_tab_width(input, 8)
+    }
+
+    pub fn parse_with_tab_width(
+        input: &str,
+        tab_width: usize,
+    ) -> Result<Worksheet, WorksheetError> {
+        fn expand_tabs(line: &str, tab_width: usize) -> Vec<char> {
+            let mut out = vec![];
+            for c in line.chars() {
+                if c == '\t' {
+                    let stop = (out.len() / tab_width + 1) * tab_width;
+                    out.resize(stop, ' ');
+                } else if c.is_whitespace() {
+                    out.push(' ');
+                } else {
+                    out.push(c);
                 }
             }
-        });
+            out
+        }
+
+        let mut lines: Vec<(usize, Vec<char>)> = input
+            .lines()
+            .map(|line| line.trim_end_matches('\r'))
+            .enumerate()
+            .map(|(j, line)| (j, expand_tabs(line, tab_width.max(1))))
+            .filter(|(_, line)| line.iter().any(|&c| c != ' '))
+            .collect();
+
+        if lines.len() < 2 {
+            return Err(WorksheetError::TooFewLines);
+        }
+        let (_, mut operator_line) = lines.pop().unwrap();
+
+        let width = lines
+            .iter()
+            .map(|(_, line)| line.len())
+            .chain([operator_line.len()])
+            .max()
+            .unwrap();
+        operator_line.resize(width, ' ');
+        for (j, line) in lines.iter_mut() {
+            line.resize(width, ' ');
+            if let Some(i) = line.iter().position(|&c| c != ' ' && !c.is_ascii_digit()) {
+                return Err(WorksheetError::InvalidCharacter {
+                    line: *j,
+                    column: i,
+                    found: line[i],
+                });
+            }
+        }
+
+        // problems are the runs of columns that contain at least one digit
+        let mut spans: Vec<std::ops::Range<usize>> = vec![];
+        for i in 0..width {
+            if lines.iter().all(|(_, line)| line[i] == ' ') {
+                continue;
+            }
+            match spans.last_mut() {
+                Some(span) if span.end == i => span.end += 1,
+                _ => spans.push(i..i + 1),
+            }
+        }
+
+        // each operator belongs to the problem its first character sits in
+        let mut operators: Vec<Option<Operator>> = vec![None; spans.len()];
+        let mut i = 0;
+        while i < width {
+            if operator_line[i] == ' ' {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < width && operator_line[i] != ' ' {
+                i += 1;
+            }
+            let token: String = operator_line[start..i].iter().collect();
+            let operator = Operator::parse(&token).ok_or(WorksheetError::UnknownOperator {
+                column: start,
+                token,
+            })?;
+            let problem = spans
+                .iter()
+                .position(|span| span.contains(&start))
+                .ok_or(WorksheetError::OperatorOutsideProblem { column: start })?;
+            let overlaps_next = spans.get(problem + 1).is_some_and(|next| next.start < i);
+            if overlaps_next || operators[problem].is_some() {
+                return Err(WorksheetError::AmbiguousOperator { column: start });
+            }
+            operators[problem] = Some(operator);
+        }
+
+        let problems = spans
+            .into_iter()
+            .zip(operators)
+            .map(|(span, operator)| match operator {
+                Some(operator) => Ok((span, operator)),
+                None => Err(WorksheetError::MissingOperator { column: span.start }),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
 
-        return (num_splits, beams_out);
+        Ok(Worksheet { lines, problems })
     }
 
-    let start_pos = input.lines().nth(0).unwrap().find('S').unwrap();
+    /// Reads the worksheet the human way: each problem has one operand per
+    /// line, written horizontally.
+    pub fn row_problems(&self) -> Result<Vec<Problem>, WorksheetError> {
+        self.problems
+            .iter()
+            .map(|(span, operator)| {
+                let operands = self
+                    .lines
+                    .iter()
+                    .map(|(j, line)| {
+                        let cells = &line[span.clone()];
+                        let first = cells.iter().position(|&c| c != ' ');
+                        let last = cells.iter().rposition(|&c| c != ' ');
+                        let (Some(first), Some(last)) = (first, last) else {
+                            return Err(WorksheetError::MissingOperand {
+                                line: *j,
+                                column: span.start,
+                            });
+                        };
+                        let digits = &cells[first..=last];
+                        if let Some(gap) = digits.iter().position(|&c| c == ' ') {
+                            return Err(WorksheetError::AmbiguousOperand {
+                                line: *j,
+                                column: span.start + first + gap,
+                            });
+                        }
+                        digits
+                            .iter()
+                            .collect::<String>()
+                            .parse::<u64>()
+                            .map_err(|_| WorksheetError::OperandOverflow {
+                                column: span.start + first,
+                            })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Problem {
+                    operator: *operator,
+                    operands,
+                })
+            })
+            .collect()
+    }
+
+    /// Reads the worksheet the cephalopod way: each character column is a
+    /// number read top to bottom, and the numbers of a problem are read right
+    /// to left.
+    pub fn column_problems(&self) -> Result<Vec<Problem>, WorksheetError> {
+        self.problems
+            .iter()
+            .map(|(span, operator)| {
+                let operands = span
+                    .clone()
+                    .rev()
+                    .map(|i| {
+                        self.lines
+                            .iter()
+                            .filter_map(|(_, line)| line[i].to_digit(10))
+                            .try_fold(0u64, |sum, digit| {
+                                sum.checked_mul(10)?.checked_add(digit as u64)
+                            })
+                            .ok_or(WorksheetError::OperandOverflow { column: i })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Problem {
+                    operator: *operator,
+                    operands,
+                })
+            })
+            .collect()
+    }
 
-    let mut num_splits = 0;
-    let mut beams = HashSet::from([start_pos]);
+    /// Renders both readings of the worksheet with their answers, so they can
+    /// be checked by eye.
+    pub fn render(&self) -> Result<String, WorksheetError> {
+        Ok(format!(
+            "row-wise:\n{}\ncolumn-wise:\n{}",
+            render_problems(&self.row_problems()?),
+            render_problems(&self.column_problems()?)
+        ))
+    }
+}
+
+/// Lays the problems out side by side like on the worksheet: operands
+/// right-aligned one per line, then the operator, a rule and the result, with
+/// the grand total on the last line.
+pub fn render_problems(problems: &[Problem]) -> String {
+    let evaluation = evaluate_problems(problems);
+    let results: Vec<String> = evaluation
+        .values
+        .iter()
+        .map(|value| match value {
+            Ok(x) => x.to_string(),
+            Err(e) => e.to_string(),
+        })
+        .collect();
+    let height = problems
+        .iter()
+        .map(|problem| problem.operands.len())
+        .max()
+        .unwrap_or(0);
+
+    // operands are aligned to the bottom, right above the operator
+    let mut grid: Vec<Vec<String>> = vec![vec![]; height + 3];
+    for (problem, result) in problems.iter().zip(&results) {
+        let padding = height - problem.operands.len();
+        for (j, cells) in grid.iter_mut().take(height).enumerate() {
+            cells.push(match j.checked_sub(padding) {
+                Some(k) => problem.operands[k].to_string(),
+                None => String::new(),
+            });
+        }
+        let width = grid[..height]
+            .iter()
+            .map(|cells| cells.last().unwrap().len())
+            .chain([problem.operator.symbol().len(), result.len()])
+            .max()
+            .unwrap();
+        grid[height].push(problem.operator.symbol().to_string());
+        grid[height + 1].push("-".repeat(width));
+        grid[height + 2].push(result.clone());
+    }
 
-    for line in input.lines().skip(1) {
-        let (delta_splits, newbeams) = beam_step(&beams, line);
-        beams = newbeams;
-        num_splits += delta_splits;
+    let widths: Vec<usize> = (0..problems.len())
+        .map(|i| grid[height + 1][i].len())
+        .collect();
+    let mut out = String::new();
+    for cells in grid {
+        let line = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:>width$}", cell))
+            .collect::<Vec<_>>()
+            .join("  ");
+        out.push_str(line.trim_end());
+        out.push('\n');
     }
+    match evaluation.total() {
+        Some(total) => out.push_str(&format!("total: {}\n", total)),
+        None => out.push_str("total: n/a\n"),
+    }
+    out
+}
 
-    num_splits
+pub fn d6_1(input: &str) -> u64 {
+    let worksheet = Worksheet::parse(input).unwrap();
+    evaluate_problems(&worksheet.row_problems().unwrap())
+        .total()
+        .unwrap()
 }
 
 #[test]
-fn test_d7_1() {
-    println!("d7_1={}", d7_1(include_str!("day7.txt")));
+fn test_d6_1() {
+    println!("d6_1={}", d6_1(include_str!("day6.txt")));
 }
 
-pub fn d7_2(input: &str) -> usize {
-    let start_pos = input.lines().nth(0).unwrap().find('S').unwrap();
-
-    // DFS, with memoization
-    fn num_splits(
-        beam_pos: usize,
-        depth: usize,
-        lines: &[&str],
-        memo: &mut HashMap<(usize, usize), usize>,
-    ) -> usize {
-        if let Some(memoed) = memo.get(&(beam_pos, depth)) {
-            return *memoed;
-        }
-
-        if lines.len() == 0 {
-            return 1;
-        }
-        if lines[0].chars().nth(beam_pos).unwrap() == '^' {
-            if beam_pos < 1 {
-                panic!();
-            } else if beam_pos >= lines[0].chars().count() - 1 {
-                panic!();
-            } else {
-                let result = num_splits(beam_pos - 1, depth + 1, &lines[1..], memo)
-                    + num_splits(beam_pos + 1, depth + 1, &lines[1..], memo);
-                memo.insert((beam_pos, depth), result);
-                return result;
-            }
-        } else {
-            let result = num_splits(beam_pos, depth + 1, &lines[1..], memo);
-            memo.insert((beam_pos, depth), result);
-            return result;
-        }
-    }
+#[test]
+fn test_d6_extended_operators() {
+    let input = "20 7  2 9 \n 4 3 10 16\n-  / max ^ \n";
+    let problems = Worksheet::parse(input).unwrap().row_problems().unwrap();
+    assert_eq!(
+        evaluate_problems(&problems).values,
+        vec![Ok(16), Ok(2), Ok(10), Ok(9u64.pow(16))]
+    );
+
+    let evaluation = evaluate_problems(&[
+        Problem {
+            operator: Operator::Subtract,
+            operands: vec![3, 4],
+        },
+        Problem {
+            operator: Operator::Divide,
+            operands: vec![3, 0],
+        },
+        Problem {
+            operator: Operator::Min,
+            operands: vec![5, 4, 6],
+        },
+        Problem {
+            operator: Operator::Power,
+            operands: vec![10, 20],
+        },
+    ]);
+    assert_eq!(
+        evaluation.errors().collect::<Vec<_>>(),
+        vec![
+            (0, EvalError::Overflow),
+            (1, EvalError::DivisionByZero),
+            (3, EvalError::Overflow)
+        ]
+    );
+    assert_eq!(evaluation.values[2], Ok(4));
+    assert_eq!(evaluation.total(), None);
+
+    assert_eq!(Operator::Power.apply(&[1, 5_000_000_000]), Ok(1));
+    assert_eq!(Operator::Power.apply(&[0, 5_000_000_000]), Ok(0));
+    assert_eq!(Operator::Power.apply(&[0, 0]), Ok(1));
+    assert_eq!(
+        Operator::Power.apply(&[2, 5_000_000_000]),
+        Err(EvalError::Overflow)
+    );
+}
 
-    num_splits(
-        start_pos,
-        0,
-        &input.lines().collect::<Vec<_>>(),
-        &mut HashMap::new(),
-    )
+pub fn d6_2(input: &str) -> u64 {
+    // Parsing the input just got harder.
+    let worksheet = Worksheet::parse(input).unwrap();
+    evaluate_problems(&worksheet.column_problems().unwrap())
+        .total()
+        .unwrap()
 }
 
 #[test]
-fn test_d7_2() {
-    println!("d7_2={}", d7_2(include_str!("day7.txt")));
+fn test_d6_2() {
+    println!("d6_2={}", d6_2(include_str!("day6.txt")));
 }
 
-pub fn d8_1(input: &str) -> usize {
-    type Position = (i64, i64, i64);
-    let positions = input
-        .lines()
-        .map(|line| {
-            let mut elements = line.split(',');
-            return (
-                elements.next().unwrap().parse::<i64>().unwrap(),
-                elements.next().unwrap().parse::<i64>().unwrap(),
-                elements.next().unwrap().parse::<i64>().unwrap(),
-            );
+#[test]
+fn test_d6_ragged_worksheet() {
+    let aligned = "123 328  51 64 \n 45 64  387 23 \n  6 98  215 314\n*   +   *   +  \n";
+    let ragged = "123 328  51 64\r\n 45 64  387 23\r\n  6 98  215 314\r\n*   +   *   +\r\n\r\n";
+    let tabbed = "123 328\t 51 64\n 45 64 \t387 23\n  6 98\t215 314\n*   +\t*   +\n";
+    let expected = Worksheet::parse(aligned).unwrap();
+    assert_eq!(
+        Worksheet::parse(ragged).unwrap().column_problems(),
+        expected.column_problems()
+    );
+    assert_eq!(
+        Worksheet::parse_with_tab_width(tabbed, 8)
+            .unwrap()
+            .row_problems(),
+        expected.row_problems()
+    );
+    assert_eq!(
+        Worksheet::parse_with_tab_width(tabbed, 4)
+            .unwrap()
+            .row_problems(),
+        expected.row_problems()
+    );
+}
+
+#[test]
+fn test_d6_render() {
+    let worksheet = Worksheet::parse("123 328\n 45 64\n  6 98\n*   -").unwrap();
+    assert_eq!(
+        worksheet.render().unwrap(),
+        [
+            "row-wise:",
+            "  123  328",
+            "   45   64",
+            "    6   98",
+            "    *    -",
+            "-----  ---",
+            "33210  166",
+            "total: 33376",
+            "",
+            "column-wise:",
+            " 356         8",
+            "  24       248",
+            "   1       369",
+            "   *         -",
+            "----  --------",
+            "8544  overflow",
+            "total: n/a",
+            "",
+        ]
+        .join("\n")
+    );
+}
+
+#[test]
+fn test_d6_ambiguous_worksheet() {
+    assert_eq!(
+        Worksheet::parse("12  3\n 4  5\n  * +"),
+        Err(WorksheetError::OperatorOutsideProblem { column: 2 })
+    );
+    assert_eq!(
+        Worksheet::parse("12 3\n4  5\n*  ++"),
+        Err(WorksheetError::UnknownOperator {
+            column: 3,
+            token: "++".to_string()
         })
-        .collect::<Vec<_>>();
+    );
+    assert_eq!(
+        Worksheet::parse("1 2\n3 4\nmax"),
+        Err(WorksheetError::AmbiguousOperator { column: 0 })
+    );
+    assert_eq!(
+        Worksheet::parse("12 3\n4  5\n*"),
+        Err(WorksheetError::MissingOperator { column: 3 })
+    );
+    assert_eq!(
+        Worksheet::parse("12 3\n4x 5\n*  +"),
+        Err(WorksheetError::InvalidCharacter {
+            line: 1,
+            column: 1,
+            found: 'x'
+        })
+    );
+    assert_eq!(
+        Worksheet::parse("1234\n1 2 \n+").unwrap().row_problems(),
+        Err(WorksheetError::AmbiguousOperand { line: 1, column: 1 })
+    );
+    assert_eq!(
+        Worksheet::parse("12\n\n+").unwrap().row_problems(),
+        Ok(vec![Problem {
+            operator: Operator::Add,
+            operands: vec![12]
+        }])
+    );
+    assert_eq!(Worksheet::parse("+"), Err(WorksheetError::TooFewLines));
+}
 
-    let mut pairs: BTreeMap<i64, (Position, Position)> = BTreeMap::new();
+/// A number of timelines. The fixed width integers report overflow instead of
+/// wrapping, `BigCount` never overflows.
+pub trait TimelineCount: Clone {
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn is_zero(&self) -> bool;
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+    /// Approximate value, for display purposes.
+    fn to_f64(&self) -> f64;
+}
 
-    // first step is to order each pair of boxes by their euclidean distance
-    for left in positions.clone() {
-        for right in positions.clone() {
-            if left != right {
-                let distance = (left.0 - right.0) * (left.0 - right.0)
-                    + (left.1 - right.1) * (left.1 - right.1)
-                    + (left.2 - right.2) * (left.2 - right.2);
-                pairs.insert(distance, (left, right));
-            }
-        }
+impl TimelineCount for u64 {
+    fn zero() -> Self {
+        0
     }
+    fn one() -> Self {
+        1
+    }
+    fn is_zero(&self) -> bool {
+        *self == 0
+    }
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        u64::checked_add(*self, *other)
+    }
+    fn to_f64(&self) -> f64 {
+        *self as f64
+    }
+}
 
-    let mut circuits: HashMap<Position, i32> = (0..positions.len())
-        .map(|i| (positions[i], -1))
-        .collect::<HashMap<_, _>>();
+impl TimelineCount for u128 {
+    fn zero() -> Self {
+        0
+    }
+    fn one() -> Self {
+        1
+    }
+    fn is_zero(&self) -> bool {
+        *self == 0
+    }
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        u128::checked_add(*self, *other)
+    }
+    fn to_f64(&self) -> f64 {
+        *self as f64
+    }
+}
 
-    let mut ngroups = 0;
+/// Only whether any timeline gets there, for when the beams' paths matter but
+/// not how many timelines follow them.
+impl TimelineCount for bool {
+    fn zero() -> Self {
+        false
+    }
+    fn one() -> Self {
+        true
+    }
+    fn is_zero(&self) -> bool {
+        !*self
+    }
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(*self || *other)
+    }
+    fn to_f64(&self) -> f64 {
+        if *self { 1.0 } else { 0.0 }
+    }
+}
 
-    for (_, (left, right)) in pairs.iter().take(1000) {
-        let lgrp = *circuits.get(left).unwrap();
-        let rgrp = *circuits.get(right).unwrap();
+/// Arbitrary precision unsigned integer, only supporting what timeline
+/// counting needs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BigCount {
+    /// Base 2^32 digits, least significant first, without trailing zeros.
+    limbs: Vec<u32>,
+}
 
-        if lgrp == -1 && rgrp == -1 {
-            circuits.insert(*left, ngroups);
-            circuits.insert(*right, ngroups);
-            ngroups += 1;
-        } else if lgrp == -1 {
-            circuits.insert(*left, rgrp);
-        } else if rgrp == -1 {
-            circuits.insert(*right, lgrp);
-        } else if rgrp == lgrp {
-        } else {
-            // both have groups -> change all entries with group == rgrp to left group
-            circuits
-                .iter_mut()
-                .filter(|(_, v)| **v == rgrp)
-                .for_each(|(_, grp)| *grp = lgrp);
+impl From<u128> for BigCount {
+    fn from(mut value: u128) -> Self {
+        let mut limbs = vec![];
+        while value > 0 {
+            limbs.push(value as u32);
+            value >>= 32;
         }
+        BigCount { limbs }
     }
+}
 
-    let mut counts = vec![];
-    for i in 0..ngroups {
-        counts.push(circuits.values().filter(|group_id| **group_id == i).count());
+impl TimelineCount for BigCount {
+    fn zero() -> Self {
+        BigCount::default()
+    }
+    fn one() -> Self {
+        BigCount { limbs: vec![1] }
+    }
+    fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        let mut limbs = Vec::with_capacity(self.limbs.len().max(other.limbs.len()) + 1);
+        let mut carry = 0u64;
+        for i in 0..self.limbs.len().max(other.limbs.len()) {
+            let sum = carry
+                + *self.limbs.get(i).unwrap_or(&0) as u64
+                + *other.limbs.get(i).unwrap_or(&0) as u64;
+            limbs.push(sum as u32);
+            carry = sum >> 32;
+        }
+        if carry > 0 {
+            limbs.push(carry as u32);
+        }
+        Some(BigCount { limbs })
+    }
+    fn to_f64(&self) -> f64 {
+        self.limbs
+            .iter()
+            .rev()
+            .fold(0.0, |value, &limb| value * 4294967296.0 + limb as f64)
     }
-    // dbg!(&counts);
-    counts.sort();
-    counts.reverse();
-    counts.iter().take(3).fold(1, |product, e| product * e)
 }
 
-#[test]
-fn test_d8_1() {
-    println!("d8_1={}", d8_1(include_str!("day8.txt")));
+impl std::fmt::Display for BigCount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // repeatedly divide by 10^9, collecting base 10^9 digits
+        let mut limbs = self.limbs.clone();
+        let mut chunks = vec![];
+        while !limbs.is_empty() {
+            let mut remainder = 0u64;
+            for limb in limbs.iter_mut().rev() {
+                let current = (remainder << 32) | *limb as u64;
+                *limb = (current / 1_000_000_000) as u32;
+                remainder = current % 1_000_000_000;
+            }
+            chunks.push(remainder);
+            while limbs.last() == Some(&0) {
+                limbs.pop();
+            }
+        }
+        match chunks.split_last() {
+            None => write!(f, "0"),
+            Some((most_significant, rest)) => {
+                write!(f, "{}", most_significant)?;
+                for chunk in rest.iter().rev() {
+                    write!(f, "{:09}", chunk)?;
+                }
+                Ok(())
+            }
+        }
+    }
 }
 
-pub fn d8_2(input: &str) -> usize {
-    type Position = (i64, i64, i64);
-    let positions = input
-        .lines()
-        .map(|line| {
-            let mut elements = line.split(',');
-            return (
-                elements.next().unwrap().parse::<i64>().unwrap(),
-                elements.next().unwrap().parse::<i64>().unwrap(),
-                elements.next().unwrap().parse::<i64>().unwrap(),
-            );
-        })
-        .collect::<Vec<_>>();
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
 
-    let mut pairs: BTreeMap<i64, (Position, Position)> = BTreeMap::new();
+impl Direction {
+    pub fn is_vertical(self) -> bool {
+        matches!(self, Direction::Up | Direction::Down)
+    }
 
-    // first step is to order each pair of boxes by their euclidean distance
-    for left in positions.clone() {
-        for right in positions.clone() {
-            if left != right {
-                let distance = (left.0 - right.0) * (left.0 - right.0)
-                    + (left.1 - right.1) * (left.1 - right.1)
-                    + (left.2 - right.2) * (left.2 - right.2);
-                pairs.insert(distance, (left, right));
-            }
+    /// (row, column) offset of one step in this direction.
+    pub fn delta(self) -> (isize, isize) {
+        match self {
+            Direction::Up => (-1, 0),
+            Direction::Down => (1, 0),
+            Direction::Left => (0, -1),
+            Direction::Right => (0, 1),
         }
     }
+}
 
-    let mut circuits: HashMap<Position, i32> = (0..positions.len())
-        .map(|i| (positions[i], -1))
-        .collect::<HashMap<_, _>>();
+/// Where a beam goes after a cell: the offset of the next cell it enters and
+/// its heading there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BeamStep {
+    pub row: isize,
+    pub column: isize,
+    pub heading: Direction,
+}
 
-    let mut ngroups = 0;
+/// The kinds of cells a manifold is built from. Adding a cell type means
+/// giving it a character and saying where it sends beams in `Cell::steps`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cell {
+    /// `.`, or the `S` start position.
+    Empty,
+    /// `^`, splits vertical beams into two beams continuing from the cells
+    /// diagonally ahead of it. Horizontal beams go through.
+    Splitter,
+    /// `<` and `>`, like a splitter but only sending the beam to one side.
+    LeftSplitter,
+    RightSplitter,
+    /// `/`
+    Mirror,
+    /// `\`
+    BackMirror,
+    /// `#`, stops beams.
+    Absorber,
+}
 
-    for (_, (left, right)) in pairs.iter() {
-        let lgrp = *circuits.get(left).unwrap();
-        let rgrp = *circuits.get(right).unwrap();
+impl Cell {
+    pub fn from_char(c: char) -> Option<Cell> {
+        match c {
+            '.' | 'S' => Some(Cell::Empty),
+            '^' => Some(Cell::Splitter),
+            '<' => Some(Cell::LeftSplitter),
+            '>' => Some(Cell::RightSplitter),
+            '/' => Some(Cell::Mirror),
+            '\\' => Some(Cell::BackMirror),
+            '#' => Some(Cell::Absorber),
+            _ => None,
+        }
+    }
 
-        if lgrp == -1 && rgrp == -1 {
-            circuits.insert(*left, ngroups);
-            circuits.insert(*right, ngroups);
-            ngroups += 1;
-        } else if lgrp == -1 {
-            circuits.insert(*left, rgrp);
-        } else if rgrp == -1 {
-            circuits.insert(*right, lgrp);
-        } else if rgrp == lgrp {
-        } else {
-            // both have groups -> change all entries with group == rgrp to left group
-            circuits
-                .iter_mut()
-                .filter(|(_, v)| **v == rgrp)
-                .for_each(|(_, grp)| *grp = lgrp);
+    pub fn to_char(self) -> char {
+        match self {
+            Cell::Empty => '.',
+            Cell::Splitter => '^',
+            Cell::LeftSplitter => '<',
+            Cell::RightSplitter => '>',
+            Cell::Mirror => '/',
+            Cell::BackMirror => '\\',
+            Cell::Absorber => '#',
         }
+    }
 
-        if circuits.values().all(|val| *val == lgrp) {
-            return (left.0 * right.0).try_into().unwrap();
+    /// Whether a beam entering this cell with the given heading is split.
+    pub fn splits(self, heading: Direction) -> bool {
+        heading.is_vertical()
+            && matches!(
+                self,
+                Cell::Splitter | Cell::LeftSplitter | Cell::RightSplitter
+            )
+    }
+
+    /// Where the beams go after entering this cell with the given heading.
+    pub fn steps(self, heading: Direction) -> Vec<BeamStep> {
+        let straight = |heading: Direction| {
+            let (row, column) = heading.delta();
+            BeamStep {
+                row,
+                column,
+                heading,
+            }
+        };
+        let diagonal = |column: isize| BeamStep {
+            row: heading.delta().0,
+            column,
+            heading,
+        };
+        if !self.splits(heading) {
+            return match (self, heading) {
+                (Cell::Absorber, _) => vec![],
+                (Cell::Mirror, Direction::Down) | (Cell::BackMirror, Direction::Up) => {
+                    vec![straight(Direction::Left)]
+                }
+                (Cell::Mirror, Direction::Up) | (Cell::BackMirror, Direction::Down) => {
+                    vec![straight(Direction::Right)]
+                }
+                (Cell::Mirror, Direction::Left) | (Cell::BackMirror, Direction::Right) => {
+                    vec![straight(Direction::Down)]
+                }
+                (Cell::Mirror, Direction::Right) | (Cell::BackMirror, Direction::Left) => {
+                    vec![straight(Direction::Up)]
+                }
+                _ => vec![straight(heading)],
+            };
+        }
+        match self {
+            Cell::LeftSplitter => vec![diagonal(-1)],
+            Cell::RightSplitter => vec![diagonal(1)],
+            _ => vec![diagonal(-1), diagonal(1)],
         }
     }
+}
 
-    0
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManifoldError {
+    MissingStart,
+    UnknownCell {
+        row: usize,
+        column: usize,
+        found: char,
+    },
 }
 
-#[test]
-fn test_d8_2() {
-    println!("d8_2={}", d8_2(include_str!("day8.txt")));
+/// A day 7 manifold. Rows shorter than the widest one are padded with empty
+/// cells.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifold {
+    pub cells: Vec<Vec<Cell>>,
+    /// (row, column) of `S`, where the beam enters heading down.
+    pub start: (usize, usize),
 }
 
-pub fn d9_1(input: &str) -> i64 {
-    let positions: Vec<(i64, i64)> = input
-        .lines()
-        .map(|line| {
-            let (xstr, ystr) = line.split_once(',').unwrap();
-            (xstr.parse::<i64>().unwrap(), ystr.parse::<i64>().unwrap())
+impl Manifold {
+    pub fn parse(input: &str) -> Result<Manifold, ManifoldError> {
+        let mut start = None;
+        let mut cells = input
+            .lines()
+            .enumerate()
+            .map(|(row, line)| {
+                line.chars()
+                    .enumerate()
+                    .map(|(column, c)| {
+                        if c == 'S' && start.is_none() {
+                            start = Some((row, column));
+                        }
+                        Cell::from_char(c).ok_or(ManifoldError::UnknownCell {
+                            row,
+                            column,
+                            found: c,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let width = cells.iter().map(|row| row.len()).max().unwrap_or(0);
+        for row in cells.iter_mut() {
+            row.resize(width, Cell::Empty);
+        }
+        Ok(Manifold {
+            cells,
+            start: start.ok_or(ManifoldError::MissingStart)?,
         })
-        .collect();
+    }
 
-    let mut top_area = 0;
-    // For each pair of positions, find the one with highest area O(n^2)
-    for p1 in &positions {
-        for p2 in &positions {
-            top_area = (((p1.0 - p2.0).abs() + 1) * ((p1.1 - p2.1).abs() + 1)).max(top_area);
-        }
+    pub fn height(&self) -> usize {
+        self.cells.len()
     }
 
-    top_area
-}
+    pub fn width(&self) -> usize {
+        self.cells.first().map_or(0, |row| row.len())
+    }
+
+    /// Where a beam taking `step` from the given cell ends up, applying the
+    /// edge policy if it goes past the left or right edge.
+    fn land(
+        &self,
+        row: usize,
+        column: usize,
+        step: BeamStep,
+        edges: EdgePolicy,
+    ) -> Result<Landing, BeamError> {
+        let width = self.width() as isize;
+        let mut heading = step.heading;
+        let mut landing_column = column as isize + step.column;
+        if landing_column < 0 || landing_column >= width {
+            match edges {
+                EdgePolicy::Drop => return Ok(Landing::Gone),
+                EdgePolicy::Wrap => landing_column = landing_column.rem_euclid(width),
+                EdgePolicy::Reflect => {
+                    landing_column = if landing_column < 0 {
+                        -1 - landing_column
+                    } else {
+                        2 * width - 1 - landing_column
+                    };
+                    heading = match heading {
+                        Direction::Left => Direction::Right,
+                        Direction::Right => Direction::Left,
+                        vertical => vertical,
+                    };
+                }
+                EdgePolicy::Error => return Err(BeamError::OutOfBounds { row, column }),
+            }
+        }
+        let landing_column = landing_column as usize;
+        Ok(match row.checked_add_signed(step.row) {
+            None => Landing::Gone,
+            Some(r) if r >= self.height() => Landing::Bottom(landing_column),
+            Some(r) => Landing::Cell((r, landing_column, heading)),
+        })
+    }
+}
+
+/// What happens to a beam that goes past the left or right edge of a
+/// manifold. Beams always leave through the top and bottom.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EdgePolicy {
+    /// The beam leaves the manifold and is lost.
+    #[default]
+    Drop,
+    /// The beam comes back in on the opposite side.
+    Wrap,
+    /// The beam bounces off the edge back into the manifold.
+    Reflect,
+    /// Fail with `BeamError::OutOfBounds`.
+    Error,
+}
+
+/// Where a beam ends up after a step.
+enum Landing {
+    Cell(BeamState),
+    /// Out through the bottom of the manifold, in this column.
+    Bottom(usize),
+    Gone,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BeamError {
+    /// The number of timelines reaching the given row does not fit in the
+    /// counter type.
+    TimelineOverflow { row: usize },
+    /// The row by row simulation only handles cells that keep beams heading
+    /// down; use `trace_beams` for this one.
+    UnsupportedCell { row: usize, column: usize },
+    /// A beam leaving this cell went past the edge of the manifold, with
+    /// `EdgePolicy::Error`.
+    OutOfBounds { row: usize, column: usize },
+    /// Beams entering this cell with this heading come back to it, so there
+    /// are infinitely many timelines.
+    Loop {
+        row: usize,
+        column: usize,
+        heading: Direction,
+    },
+}
+
+/// What happens to a tachyon beam on its way through a day 7 manifold.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BeamCounts<T = u128> {
+    /// Number of splitters hit by at least one beam.
+    pub splits: usize,
+    /// Number of distinct paths a single particle can take through the
+    /// manifold.
+    pub timelines: T,
+}
+
+/// Propagates the beam from `S` down the manifold one row at a time, carrying
+/// the number of timelines that reach each column.
+///
+/// Timelines double with every row of splitters, so pick `u128` or
+/// `BigCount` as the counter for tall manifolds.
+pub fn simulate_beams<T: TimelineCount>(
+    manifold: &Manifold,
+    edges: EdgePolicy,
+) -> Result<BeamCounts<T>, BeamError> {
+    let (start_row, start_column) = manifold.start;
+    let mut timelines = vec![T::zero(); manifold.width()];
+    timelines[start_column] = T::one();
+    let mut splits = 0;
+
+    for (row, cells) in manifold.cells.iter().enumerate().skip(start_row) {
+        let overflow = BeamError::TimelineOverflow { row };
+        let mut next = vec![T::zero(); manifold.width()];
+        for (column, (cell, count)) in cells.iter().zip(&timelines).enumerate() {
+            if count.is_zero() {
+                continue;
+            }
+            if cell.splits(Direction::Down) {
+                splits += 1;
+            }
+            for step in cell.steps(Direction::Down) {
+                if step.heading != Direction::Down {
+                    return Err(BeamError::UnsupportedCell { row, column });
+                }
+                match manifold.land(row, column, step, edges)? {
+                    Landing::Cell((_, i, _)) | Landing::Bottom(i) => {
+                        next[i] = next[i].checked_add(count).ok_or(overflow.clone())?;
+                    }
+                    Landing::Gone => {}
+                }
+            }
+        }
+        timelines = next;
+    }
+
+    let total = timelines
+        .iter()
+        .try_fold(T::zero(), |sum, count| sum.checked_add(count))
+        .ok_or(BeamError::TimelineOverflow {
+            row: manifold.height() - 1,
+        })?;
+    Ok(BeamCounts {
+        splits,
+        timelines: total,
+    })
+}
+
+/// Follows beams in every direction through the manifold, for manifolds whose
+/// mirrors send beams sideways or back up. Only beams leaving through the
+/// bottom complete a timeline: beams leaving through the top are dropped, and
+/// absorbed beams end theirs without counting.
+pub fn trace_beams<T: TimelineCount>(
+    manifold: &Manifold,
+    edges: EdgePolicy,
+) -> Result<BeamCounts<T>, BeamError> {
+    trace_states(manifold, edges).map(|(counts, _)| counts)
+}
+
+/// A beam entering a cell (row, column) with a given heading.
+type BeamState = (usize, usize, Direction);
+
+/// Runs `trace_beams`, also returning the number of timelines reaching each
+/// state.
+fn trace_states<T: TimelineCount>(
+    manifold: &Manifold,
+    edges: EdgePolicy,
+) -> Result<(BeamCounts<T>, HashMap<BeamState, T>), BeamError> {
+    type State = BeamState;
+    let start = (manifold.start.0, manifold.start.1, Direction::Down);
+
+    // find every reachable state and where its beams go next, `None` meaning
+    // out through the bottom
+    let mut successors: HashMap<State, Vec<Option<State>>> = HashMap::new();
+    let mut predecessors: HashMap<State, Vec<State>> = HashMap::new();
+    let mut split_cells = HashSet::new();
+    let mut queue = VecDeque::from([start]);
+    while let Some(state) = queue.pop_front() {
+        if successors.contains_key(&state) {
+            continue;
+        }
+        let (row, column, heading) = state;
+        let cell = manifold.cells[row][column];
+        if cell.splits(heading) {
+            split_cells.insert((row, column));
+        }
+        let mut next = vec![];
+        for step in cell.steps(heading) {
+            match manifold.land(row, column, step, edges)? {
+                Landing::Cell(next_state) => {
+                    predecessors.entry(next_state).or_default().push(state);
+                    queue.push_back(next_state);
+                    next.push(Some(next_state));
+                }
+                Landing::Bottom(_) => next.push(None),
+                Landing::Gone => {}
+            }
+        }
+        successors.insert(state, next);
+    }
+
+    // count the paths reaching each state, in topological order
+    let mut in_degree: HashMap<State, usize> = predecessors
+        .iter()
+        .map(|(state, from)| (*state, from.len()))
+        .collect();
+    let mut counts: HashMap<State, T> = HashMap::from([(start, T::one())]);
+    let mut ready = if in_degree.contains_key(&start) {
+        vec![]
+    } else {
+        vec![start]
+    };
+    let mut done = HashSet::new();
+    let mut timelines = T::zero();
+    while let Some(state) = ready.pop() {
+        done.insert(state);
+        let count = counts[&state].clone();
+        let overflow = BeamError::TimelineOverflow { row: state.0 };
+        for next in &successors[&state] {
+            let Some(next) = next else {
+                timelines = timelines.checked_add(&count).ok_or(overflow.clone())?;
+                continue;
+            };
+            let entry = counts.entry(*next).or_insert_with(T::zero);
+            *entry = entry.checked_add(&count).ok_or(overflow.clone())?;
+            let degree = in_degree.get_mut(next).unwrap();
+            *degree -= 1;
+            if *degree == 0 {
+                ready.push(*next);
+            }
+        }
+    }
+
+    if done.len() < successors.len() {
+        // every state left over has a predecessor left over, so walking back
+        // from any of them eventually goes round a loop
+        let left_over = |state: &&State| !done.contains(*state);
+        let mut state = *successors.keys().filter(left_over).min().unwrap();
+        let mut seen = HashSet::new();
+        while seen.insert(state) {
+            state = *predecessors[&state].iter().filter(left_over).min().unwrap();
+        }
+        let (row, column, heading) = state;
+        return Err(BeamError::Loop {
+            row,
+            column,
+            heading,
+        });
+    }
+
+    let beam_counts = BeamCounts {
+        splits: split_cells.len(),
+        timelines,
+    };
+    Ok((beam_counts, counts))
+}
+
+/// The beams going through one cell of a manifold.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CellBeams<T = u128> {
+    pub vertical: bool,
+    pub horizontal: bool,
+    /// Number of timelines going through the cell.
+    pub timelines: T,
+}
+
+/// Traces the beams like `trace_beams`, keeping track of where they go.
+pub fn beam_paths<T: TimelineCount>(
+    manifold: &Manifold,
+    edges: EdgePolicy,
+) -> Result<Vec<Vec<CellBeams<T>>>, BeamError> {
+    let (_, counts) = trace_states::<T>(manifold, edges)?;
+    let mut cells = vec![
+        vec![
+            CellBeams {
+                vertical: false,
+                horizontal: false,
+                timelines: T::zero(),
+            };
+            manifold.width()
+        ];
+        manifold.height()
+    ];
+    for ((row, column, heading), count) in counts {
+        let cell = &mut cells[row][column];
+        if heading.is_vertical() {
+            cell.vertical = true;
+        } else {
+            cell.horizontal = true;
+        }
+        cell.timelines = cell
+            .timelines
+            .checked_add(&count)
+            .ok_or(BeamError::TimelineOverflow { row })?;
+    }
+    Ok(cells)
+}
+
+/// Draws the manifold with the beams over its empty cells: `|` for vertical
+/// beams, `-` for horizontal ones and `+` where they cross. With `annotate`,
+/// cells are widened and empty cells with beams show how many timelines go
+/// through them instead.
+pub fn render_beams<T: TimelineCount + std::fmt::Display>(
+    manifold: &Manifold,
+    paths: &[Vec<CellBeams<T>>],
+    annotate: bool,
+) -> String {
+    let symbols: Vec<Vec<String>> = manifold
+        .cells
+        .iter()
+        .zip(paths)
+        .enumerate()
+        .map(|(row, (cells, beams))| {
+            cells
+                .iter()
+                .zip(beams)
+                .enumerate()
+                .map(|(column, (cell, beams))| {
+                    if (row, column) == manifold.start {
+                        "S".to_string()
+                    } else if *cell != Cell::Empty {
+                        cell.to_char().to_string()
+                    } else if annotate && (beams.vertical || beams.horizontal) {
+                        beams.timelines.to_string()
+                    } else {
+                        match (beams.vertical, beams.horizontal) {
+                            (true, true) => "+",
+                            (true, false) => "|",
+                            (false, true) => "-",
+                            (false, false) => ".",
+                        }
+                        .to_string()
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    let width = symbols.iter().flatten().map(|s| s.len()).max().unwrap_or(1);
+    let separator = if annotate { " " } else { "" };
+    let mut out = String::new();
+    for line in symbols {
+        let line: Vec<String> = line.iter().map(|s| format!("{:>width$}", s)).collect();
+        out.push_str(&line.join(separator));
+        out.push('\n');
+    }
+    out
+}
+
+/// SVG heatmap of how many timelines go through each cell, on a log scale from
+/// dark blue to yellow. Cells that aren't empty are drawn with their
+/// character on top.
+pub fn beam_heatmap_svg<T: TimelineCount>(
+    manifold: &Manifold,
+    paths: &[Vec<CellBeams<T>>],
+    cell_size: usize,
+) -> String {
+    let max = paths
+        .iter()
+        .flatten()
+        .map(|beams| beams.timelines.to_f64())
+        .fold(0.0, f64::max);
+    let (width, height) = (manifold.width() * cell_size, manifold.height() * cell_size);
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n"
+    );
+    svg.push_str(&format!(
+        "<rect width=\"{width}\" height=\"{height}\" fill=\"black\"/>\n"
+    ));
+    for (row, (cells, beams)) in manifold.cells.iter().zip(paths).enumerate() {
+        for (column, (cell, beams)) in cells.iter().zip(beams).enumerate() {
+            let (x, y) = (column * cell_size, row * cell_size);
+            if !beams.timelines.is_zero() {
+                let t = (1.0 + beams.timelines.to_f64()).ln() / (1.0 + max).ln();
+                svg.push_str(&format!(
+                    "<rect x=\"{x}\" y=\"{y}\" width=\"{cell_size}\" height=\"{cell_size}\" fill=\"rgb({},{},{})\"/>\n",
+                    (255.0 * t) as u8,
+                    (40.0 + 200.0 * t) as u8,
+                    (160.0 * (1.0 - t)) as u8,
+                ));
+            }
+            if *cell != Cell::Empty {
+                let glyph = match cell.to_char() {
+                    '<' => "&lt;".to_string(),
+                    '>' => "&gt;".to_string(),
+                    c => c.to_string(),
+                };
+                svg.push_str(&format!(
+                    "<text x=\"{}\" y=\"{}\" font-size=\"{cell_size}\" font-family=\"monospace\" text-anchor=\"middle\" fill=\"white\">{glyph}</text>\n",
+                    x + cell_size / 2,
+                    y + cell_size * 4 / 5,
+                ));
+            }
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+pub fn d7_1(input: &str) -> usize {
+    // the question is how many splitters the beam hits.
+    // splits don't depend on the timeline counts, so only track where beams go
+    let manifold = Manifold::parse(input).unwrap();
+    simulate_beams::<bool>(&manifold, EdgePolicy::default())
+        .unwrap()
+        .splits
+}
+
+#[test]
+fn test_d7_1() {
+    println!("d7_1={}", d7_1(include_str!("day7.txt")));
+}
+
+pub fn d7_2(input: &str) -> usize {
+    let manifold = Manifold::parse(input).unwrap();
+    simulate_beams::<u128>(&manifold, EdgePolicy::default())
+        .unwrap()
+        .timelines
+        .try_into()
+        .unwrap()
+}
+
+#[test]
+fn test_d7_2() {
+    println!("d7_2={}", d7_2(include_str!("day7.txt")));
+}
+
+#[test]
+fn test_d7_simulate_beams() {
+    let example = include_str!("day7.txt");
+    assert_eq!(
+        simulate_beams::<u64>(&Manifold::parse(example).unwrap(), EdgePolicy::Drop),
+        Ok(BeamCounts {
+            splits: 21,
+            timelines: 40
+        })
+    );
+    assert_eq!(
+        simulate_beams::<bool>(&Manifold::parse(example).unwrap(), EdgePolicy::Drop),
+        Ok(BeamCounts {
+            splits: 21,
+            timelines: true
+        })
+    );
+
+    // tall enough to overflow the stack of a recursive search
+    let tall = format!("S..\n{}", "...\n^..\n".repeat(200_000));
+    assert_eq!(
+        simulate_beams::<u64>(&Manifold::parse(&tall).unwrap(), EdgePolicy::Drop),
+        Ok(BeamCounts {
+            splits: 1,
+            timelines: 1
+        })
+    );
+}
+
+#[test]
+fn test_d7_timeline_overflow() {
+    // every row of splitters doubles the timelines, and the manifold is wide
+    // enough that no beam reaches the edges
+    let rows = 140;
+    let width = 2 * rows + 1;
+    let mut manifold = format!("{}S{}\n", ".".repeat(rows), ".".repeat(rows));
+    for row in 0..rows {
+        let line: String = (0..width)
+            .map(|i| if (i + row) % 2 == 0 { '^' } else { '.' })
+            .collect();
+        manifold.push_str(&line);
+        manifold.push('\n');
+    }
+
+    let manifold = Manifold::parse(&manifold).unwrap();
+    assert_eq!(
+        simulate_beams::<u64>(&manifold, EdgePolicy::Drop),
+        Err(BeamError::TimelineOverflow { row: 68 })
+    );
+    assert_eq!(
+        simulate_beams::<u128>(&manifold, EdgePolicy::Drop),
+        Err(BeamError::TimelineOverflow { row: 132 })
+    );
+    let big = simulate_beams::<BigCount>(&manifold, EdgePolicy::Drop).unwrap();
+    assert_eq!(
+        big.timelines.to_string(),
+        "1393796574908163946345982392040522594123776"
+    );
+    let mut shorter = manifold.clone();
+    shorter.cells.truncate(rows + 1 - 13);
+    assert_eq!(
+        simulate_beams::<u128>(&shorter, EdgePolicy::Drop)
+            .unwrap()
+            .timelines,
+        1 << 127
+    );
+    assert_eq!(
+        trace_beams::<u128>(&shorter, EdgePolicy::Drop),
+        simulate_beams(&shorter, EdgePolicy::Drop)
+    );
+    assert_eq!(BigCount::from(1_000_000_000u128).to_string(), "1000000000");
+    assert_eq!(BigCount::zero().to_string(), "0");
+}
+
+#[test]
+fn test_d7_cell_types() {
+    let example = Manifold::parse(include_str!("day7.txt")).unwrap();
+    assert_eq!(
+        trace_beams::<u64>(&example, EdgePolicy::Drop),
+        simulate_beams(&example, EdgePolicy::Drop)
+    );
+
+    let mirrors = Manifold::parse("S....\n\\..\\.\n...^.\n.....").unwrap();
+    assert_eq!(
+        trace_beams::<u64>(&mirrors, EdgePolicy::Drop),
+        Ok(BeamCounts {
+            splits: 1,
+            timelines: 2
+        })
+    );
+    assert_eq!(
+        simulate_beams::<u64>(&mirrors, EdgePolicy::Drop),
+        Err(BeamError::UnsupportedCell { row: 1, column: 0 })
+    );
+
+    let one_sided = Manifold::parse(".S..\n.>..\n..<.\n.#..\n....").unwrap();
+    let expected = Ok(BeamCounts {
+        splits: 2,
+        timelines: 0,
+    });
+    assert_eq!(
+        simulate_beams::<u64>(&one_sided, EdgePolicy::Drop),
+        expected
+    );
+    assert_eq!(trace_beams::<u64>(&one_sided, EdgePolicy::Drop), expected);
+
+    let looping = Manifold::parse("..S..\n./>\\.\n.....\n.\\./.").unwrap();
+    assert_eq!(
+        trace_beams::<u64>(&looping, EdgePolicy::Drop),
+        Err(BeamError::Loop {
+            row: 1,
+            column: 1,
+            heading: Direction::Up
+        })
+    );
+
+    assert_eq!(
+        Manifold::parse("S\n.\n?"),
+        Err(ManifoldError::UnknownCell {
+            row: 2,
+            column: 0,
+            found: '?'
+        })
+    );
+    assert_eq!(Manifold::parse("..\n^."), Err(ManifoldError::MissingStart));
+}
+
+#[test]
+fn test_d7_render_beams() {
+    let manifold = Manifold::parse("..S..\n.....\n..^..\n.....\n.^.\\.\n.../.").unwrap();
+    let paths = beam_paths::<u64>(&manifold, EdgePolicy::Drop).unwrap();
+    assert_eq!(
+        render_beams(&manifold, &paths, false),
+        ["..S..", "..|..", "..^..", ".|.|.", ".^.\\-", "|.|/."]
+            .map(|line| format!("{}\n", line))
+            .concat()
+    );
+    assert_eq!(
+        render_beams(&manifold, &paths, true),
+        [
+            ". . S . .",
+            ". . 1 . .",
+            ". . ^ . .",
+            ". 1 . 1 .",
+            ". ^ . \\ 1",
+            "1 . 1 / .",
+        ]
+        .map(|line| format!("{}\n", line))
+        .concat()
+    );
+
+    let svg = beam_heatmap_svg(&manifold, &paths, 10);
+    assert!(
+        svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"50\" height=\"60\"")
+    );
+    assert_eq!(svg.matches("<rect").count(), 1 + 10);
+    assert_eq!(svg.matches("<text").count(), 4);
+    assert!(svg.ends_with("</svg>\n"));
+}
+
+#[test]
+fn test_d7_edge_policies() {
+    let manifold = Manifold::parse("S..\n^..\n...").unwrap();
+    for (edges, timelines) in [
+        (EdgePolicy::Drop, 1),
+        (EdgePolicy::Wrap, 2),
+        (EdgePolicy::Reflect, 2),
+    ] {
+        let expected = Ok(BeamCounts {
+            splits: 1,
+            timelines,
+        });
+        assert_eq!(simulate_beams::<u64>(&manifold, edges), expected);
+        assert_eq!(trace_beams::<u64>(&manifold, edges), expected);
+    }
+    let out_of_bounds = Err(BeamError::OutOfBounds { row: 1, column: 0 });
+    assert_eq!(
+        simulate_beams::<u64>(&manifold, EdgePolicy::Error),
+        out_of_bounds
+    );
+    assert_eq!(
+        trace_beams::<u64>(&manifold, EdgePolicy::Error),
+        out_of_bounds
+    );
+
+    // a horizontal beam wrapping around keeps its heading, a reflected one
+    // turns back
+    let mirror = Manifold::parse(".S\n./\n..").unwrap();
+    let expected = [
+        (EdgePolicy::Drop, 0),
+        (EdgePolicy::Wrap, 1),
+        (EdgePolicy::Reflect, 0),
+    ];
+    for (edges, timelines) in expected {
+        assert_eq!(
+            trace_beams::<u64>(&mirror, edges).unwrap().timelines,
+            timelines
+        );
+    }
+}
+
+/// Position of a day 8 junction box.
+pub type Position = (i64, i64, i64);
+
+pub fn parse_positions(input: &str) -> Vec<Position> {
+    input
+        .lines()
+        .map(|line| {
+            let mut elements = line.split(',');
+            (
+                elements.next().unwrap().parse::<i64>().unwrap(),
+                elements.next().unwrap().parse::<i64>().unwrap(),
+                elements.next().unwrap().parse::<i64>().unwrap(),
+            )
+        })
+        .collect()
+}
+
+/// A way of measuring how far apart two junction boxes are. Distances must be
+/// symmetric and never negative.
+///
+/// Any `Fn(&Position, &Position) -> i64` closure is a metric too.
+pub trait Metric {
+    fn distance(&self, a: &Position, b: &Position) -> i64;
+
+    /// A lower bound of the distance between two boxes whose coordinates
+    /// differ by `delta` along one axis, if there is one. This is what lets
+    /// `KdTree` skip the boxes that are too far away.
+    fn axis_bound(&self, _delta: i64) -> Option<i64> {
+        None
+    }
+}
+
+/// Squared straight line distance, the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SquaredEuclidean;
+
+/// Sum of the distances along each axis, for cables following axis-aligned
+/// trays.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Manhattan;
+
+/// Largest of the distances along each axis.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Chebyshev;
+
+impl Metric for SquaredEuclidean {
+    fn distance(&self, a: &Position, b: &Position) -> i64 {
+        (a.0 - b.0) * (a.0 - b.0) + (a.1 - b.1) * (a.1 - b.1) + (a.2 - b.2) * (a.2 - b.2)
+    }
+
+    fn axis_bound(&self, delta: i64) -> Option<i64> {
+        Some(delta * delta)
+    }
+}
+
+impl Metric for Manhattan {
+    fn distance(&self, a: &Position, b: &Position) -> i64 {
+        (a.0 - b.0).abs() + (a.1 - b.1).abs() + (a.2 - b.2).abs()
+    }
+
+    fn axis_bound(&self, delta: i64) -> Option<i64> {
+        Some(delta.abs())
+    }
+}
+
+impl Metric for Chebyshev {
+    fn distance(&self, a: &Position, b: &Position) -> i64 {
+        (a.0 - b.0)
+            .abs()
+            .max((a.1 - b.1).abs())
+            .max((a.2 - b.2).abs())
+    }
+
+    fn axis_bound(&self, delta: i64) -> Option<i64> {
+        Some(delta.abs())
+    }
+}
+
+impl<F: Fn(&Position, &Position) -> i64> Metric for F {
+    fn distance(&self, a: &Position, b: &Position) -> i64 {
+        self(a, b)
+    }
+}
+
+/// Two junction boxes, by index in the input, and the distance between them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BoxPair {
+    pub distance: i64,
+    pub left: usize,
+    pub right: usize,
+}
+
+/// Every pair of boxes once, with `left < right`, closest first. Pairs at the
+/// same distance are ordered by `left` then `right`.
+pub fn sorted_pairs<M: Metric + ?Sized>(positions: &[Position], metric: &M) -> Vec<BoxPair> {
+    let mut pairs = vec![];
+    for (left, l) in positions.iter().enumerate() {
+        for (right, r) in positions.iter().enumerate().skip(left + 1) {
+            pairs.push(BoxPair {
+                distance: metric.distance(l, r),
+                left,
+                right,
+            });
+        }
+    }
+    pairs.sort();
+    pairs
+}
+
+/// A minimum spanning tree of junction boxes, as built by Kruskal's algorithm.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpanningTree {
+    /// The pairs that got connected, in the order they were accepted.
+    pub edges: Vec<BoxPair>,
+    /// Sum of the distances of `edges`.
+    pub total_weight: i64,
+    /// The pair whose connection joined all boxes into a single circuit, or
+    /// `None` with fewer than two boxes.
+    pub completing_edge: Option<BoxPair>,
+}
+
+/// Connects the closest pairs of boxes first, skipping pairs that are already
+/// in the same circuit, until all boxes are in one circuit.
+pub fn minimum_spanning_tree<M: Metric + ?Sized>(
+    positions: &[Position],
+    metric: &M,
+) -> SpanningTree {
+    let mut circuits: DisjointSet<usize> = (0..positions.len()).collect();
+    let mut tree = SpanningTree {
+        edges: vec![],
+        total_weight: 0,
+        completing_edge: None,
+    };
+    if positions.len() < 2 {
+        return tree;
+    }
+    for pair in KdTree::new(positions, metric).pairs() {
+        if !circuits.union(&pair.left, &pair.right) {
+            continue;
+        }
+        tree.edges.push(pair);
+        tree.total_weight += pair.distance;
+        if circuits.component_count() == 1 {
+            tree.completing_edge = Some(pair);
+            break;
+        }
+    }
+    tree
+}
+
+/// How junction boxes are grouped into circuits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CircuitStats {
+    /// Size of every circuit, largest first, including single boxes.
+    pub sizes: Vec<usize>,
+}
+
+impl CircuitStats {
+    pub fn circuit_count(&self) -> usize {
+        self.sizes.len()
+    }
+
+    /// Number of boxes that aren't connected to any other.
+    pub fn singletons(&self) -> usize {
+        self.sizes.iter().filter(|&&size| size == 1).count()
+    }
+
+    /// Number of circuits of each size.
+    pub fn size_distribution(&self) -> BTreeMap<usize, usize> {
+        let mut distribution = BTreeMap::new();
+        for &size in &self.sizes {
+            *distribution.entry(size).or_insert(0) += 1;
+        }
+        distribution
+    }
+
+    /// Product of the sizes of the `n` largest circuits.
+    pub fn largest_product(&self, n: usize) -> usize {
+        self.sizes.iter().take(n).product()
+    }
+}
+
+/// Connects the `connections` closest pairs of boxes, including pairs that
+/// are already in the same circuit, and reports the resulting circuits.
+pub fn analyse_circuits<M: Metric + ?Sized>(
+    positions: &[Position],
+    connections: usize,
+    metric: &M,
+) -> CircuitStats {
+    let mut circuits: DisjointSet<usize> = (0..positions.len()).collect();
+    for pair in KdTree::new(positions, metric).pairs().take(connections) {
+        circuits.union(&pair.left, &pair.right);
+    }
+    CircuitStats {
+        sizes: circuits.component_sizes(),
+    }
+}
+
+pub fn d8_1(input: &str) -> usize {
+    let positions = parse_positions(input);
+    analyse_circuits(&positions, 1000, &SquaredEuclidean).largest_product(3)
+}
+
+#[test]
+fn test_d8_1() {
+    println!("d8_1={}", d8_1(include_str!("day8.txt")));
+}
+
+pub fn d8_2(input: &str) -> usize {
+    // connecting pairs until there is a single circuit is building a minimum
+    // spanning tree, we want the last edge of it
+    let positions = parse_positions(input);
+    let last = minimum_spanning_tree(&positions, &SquaredEuclidean)
+        .completing_edge
+        .unwrap();
+    (positions[last.left].0 * positions[last.right].0)
+        .try_into()
+        .unwrap()
+}
+
+#[test]
+fn test_d8_2() {
+    println!("d8_2={}", d8_2(include_str!("day8.txt")));
+}
+
+#[test]
+fn test_d8_equidistant_pairs() {
+    // a unit square and a box further away: four pairs at distance 1
+    let input = "0,0,0\n1,0,0\n0,1,0\n1,1,0\n10,0,0";
+    let pair = |distance, left, right| BoxPair {
+        distance,
+        left,
+        right,
+    };
+    let pairs = sorted_pairs(&parse_positions(input), &SquaredEuclidean);
+    assert_eq!(
+        pairs[..7],
+        [
+            pair(1, 0, 1),
+            pair(1, 0, 2),
+            pair(1, 1, 3),
+            pair(1, 2, 3),
+            pair(2, 0, 3),
+            pair(2, 1, 2),
+            pair(81, 1, 4),
+        ]
+    );
+    assert_eq!(pairs.len(), 10);
+    assert_eq!(d8_1(input), 5);
+    assert_eq!(d8_2(input), 10);
+
+    // the same boxes in another order give the same distances
+    let shuffled = "10,0,0\n1,1,0\n0,1,0\n1,0,0\n0,0,0";
+    let distances = |input| {
+        sorted_pairs(&parse_positions(input), &SquaredEuclidean)
+            .iter()
+            .map(|pair| pair.distance)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(distances(shuffled), distances(input));
+    assert_eq!(d8_2(shuffled), 10);
+}
+
+#[test]
+fn test_d8_circuit_stats() {
+    let positions = parse_positions(include_str!("day8.txt"));
+
+    let stats = analyse_circuits(&positions, 10, &SquaredEuclidean);
+    assert_eq!(stats.sizes, [5, 4, 2, 2, 1, 1, 1, 1, 1, 1, 1]);
+    assert_eq!(stats.circuit_count(), 11);
+    assert_eq!(stats.singletons(), 7);
+    assert_eq!(
+        stats.size_distribution(),
+        BTreeMap::from([(1, 7), (2, 2), (4, 1), (5, 1)])
+    );
+    assert_eq!(stats.largest_product(3), 40);
+
+    let unconnected = analyse_circuits(&positions, 0, &SquaredEuclidean);
+    assert_eq!(unconnected.sizes, vec![1; 20]);
+    assert_eq!(
+        analyse_circuits(&positions, 1000, &SquaredEuclidean).sizes,
+        vec![20]
+    );
+}
+
+#[test]
+fn test_d8_metrics() {
+    // a-b is (3, 3), a-c is (5, 0) and b-c is (2, 3) along x and y
+    let positions = [(0, 0, 0), (3, 3, 0), (5, 0, 0)];
+    let order = |metric: &dyn Metric| {
+        sorted_pairs(&positions, metric)
+            .iter()
+            .map(|pair| (pair.distance, pair.left, pair.right))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        order(&SquaredEuclidean),
+        [(13, 1, 2), (18, 0, 1), (25, 0, 2)]
+    );
+    assert_eq!(order(&Manhattan), [(5, 0, 2), (5, 1, 2), (6, 0, 1)]);
+    assert_eq!(order(&Chebyshev), [(3, 0, 1), (3, 1, 2), (5, 0, 2)]);
+    let along_x = |a: &Position, b: &Position| (a.0 - b.0).abs();
+    assert_eq!(order(&along_x), [(2, 1, 2), (3, 0, 1), (5, 0, 2)]);
+
+    let tree = minimum_spanning_tree(&positions, &Manhattan);
+    assert_eq!(tree.total_weight, 10);
+    assert_eq!(
+        tree.completing_edge,
+        Some(BoxPair {
+            distance: 5,
+            left: 1,
+            right: 2
+        })
+    );
+    assert_eq!(analyse_circuits(&positions, 1, &Chebyshev).sizes, [2, 1]);
+}
+
+#[test]
+fn test_d8_minimum_spanning_tree() {
+    let positions = parse_positions("0,0,0\n1,0,0\n0,1,0\n1,1,0\n10,0,0");
+    let pair = |distance, left, right| BoxPair {
+        distance,
+        left,
+        right,
+    };
+    assert_eq!(
+        minimum_spanning_tree(&positions, &SquaredEuclidean),
+        SpanningTree {
+            edges: vec![pair(1, 0, 1), pair(1, 0, 2), pair(1, 1, 3), pair(81, 1, 4)],
+            total_weight: 84,
+            completing_edge: Some(pair(81, 1, 4)),
+        }
+    );
+
+    let positions = parse_positions(include_str!("day8.txt"));
+    let tree = minimum_spanning_tree(&positions, &SquaredEuclidean);
+    assert_eq!(tree.edges.len(), positions.len() - 1);
+    assert_eq!(tree.completing_edge, tree.edges.last().copied());
+
+    assert_eq!(
+        minimum_spanning_tree(&[(1, 2, 3)], &SquaredEuclidean).completing_edge,
+        None
+    );
+}
+
+pub fn parse_red_tiles(input: &str) -> Vec<Point> {
+    input
+        .lines()
+        .map(|line| {
+            let (xstr, ystr) = line.split_once(',').unwrap();
+            Point {
+                x: xstr.parse::<i64>().unwrap(),
+                y: ystr.parse::<i64>().unwrap(),
+            }
+        })
+        .collect()
+}
+
+/// A rectangle with red tiles at two opposite corners.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RedRectangle {
+    /// Number of tiles covered, edges included.
+    pub area: i64,
+    pub corners: (Point, Point),
+}
+
+/// Every rectangle with red tiles at two opposite corners, largest first.
+/// Rectangles of the same area are ordered by the positions of their corners
+/// in the input, so the first corner is always earlier than the second.
+pub fn red_rectangles(positions: &[Point]) -> Vec<RedRectangle> {
+    let mut rectangles = vec![];
+    for (i, p1) in positions.iter().enumerate() {
+        for p2 in &positions[i + 1..] {
+            rectangles.push(RedRectangle {
+                area: ((p1.x - p2.x).abs() + 1) * ((p1.y - p2.y).abs() + 1),
+                corners: (*p1, *p2),
+            });
+        }
+    }
+    // stable, so ties keep the enumeration order
+    rectangles.sort_by_key(|rectangle| Reverse(rectangle.area));
+    rectangles
+}
+
+pub fn d9_1(input: &str) -> i64 {
+    red_rectangles(&parse_red_tiles(input))
+        .first()
+        .map_or(0, |rectangle| rectangle.area)
+}
+
+#[test]
+fn test_d9_1() {
+    println!("d9_1={}", d9_1(include_str!("day9.txt")));
+}
+
+/// Diagnostics reported by [`largest_red_rectangle`] as it goes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FloorEvent {
+    /// Extent of the loop of red tiles.
+    BoundingBox { min: Point, max: Point },
+    /// A candidate rectangle that isn't entirely red or green.
+    Rejected(RedRectangle),
+    /// The largest rectangle that is.
+    Found(RedRectangle),
+}
+
+pub fn d9_2(input: &str) -> i64 {
+    largest_red_rectangle(&parse_red_tiles(input), |_| {}).map_or(0, |rectangle| rectangle.area)
+}
+
+/// The largest rectangle with red tiles at two opposite corners that only
+/// covers red or green tiles, calling `log` with progress along the way.
+pub fn largest_red_rectangle(
+    positions: &[Point],
+    mut log: impl FnMut(&FloorEvent),
+) -> Option<RedRectangle> {
+    let polygon = RectilinearPolygon::new(positions.to_vec()).unwrap();
+    let (min, max) = polygon.bounding_box();
+    log(&FloorEvent::BoundingBox { min, max });
+
+    let index = ContainmentIndex::new(&polygon);
+
+    // Check the largest rectangles first: the first one entirely in the path
+    // is the answer.
+    for rectangle in red_rectangles(positions) {
+        if index.contains_rectangle(rectangle.corners.0, rectangle.corners.1) {
+            log(&FloorEvent::Found(rectangle));
+            return Some(rectangle);
+        }
+        log(&FloorEvent::Rejected(rectangle));
+    }
+
+    None
+}
+
+/// Draws the floor as an SVG, `size` pixels along its longer side: green for
+/// the tiles inside the loop, red for the red tiles and, if given, the
+/// outline of `best` in blue.
+pub fn floor_svg(polygon: &RectilinearPolygon, best: Option<&RedRectangle>, size: usize) -> String {
+    let (min, max) = polygon.bounding_box();
+    let (columns, rows) = (max.x - min.x + 1, max.y - min.y + 1);
+    let scale = size as f64 / columns.max(rows) as f64;
+    let (width, height) = (
+        (columns as f64 * scale).round() as usize,
+        (rows as f64 * scale).round() as usize,
+    );
+    // keep red tiles visible when a tile is much smaller than a pixel
+    let marker = (2.0 / scale).max(0.5);
+
+    // tile (x, y) spans x..x + 1, so the loop runs through tile centres
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"{} {} {columns} {rows}\">\n",
+        min.x, min.y
+    );
+    svg.push_str(&format!(
+        "<rect x=\"{}\" y=\"{}\" width=\"{columns}\" height=\"{rows}\" fill=\"black\"/>\n",
+        min.x, min.y
+    ));
+    let points: Vec<String> = polygon
+        .vertices()
+        .iter()
+        .map(|p| format!("{},{}", p.x as f64 + 0.5, p.y as f64 + 0.5))
+        .collect();
+    // a one tile wide stroke covers the outer half of the boundary tiles
+    svg.push_str(&format!(
+        "<polygon points=\"{}\" fill=\"green\" stroke=\"green\" stroke-width=\"1\" stroke-linejoin=\"miter\"/>\n",
+        points.join(" ")
+    ));
+    for p in polygon.vertices() {
+        svg.push_str(&format!(
+            "<circle cx=\"{}\" cy=\"{}\" r=\"{marker}\" fill=\"red\"/>\n",
+            p.x as f64 + 0.5,
+            p.y as f64 + 0.5
+        ));
+    }
+    if let Some(best) = best {
+        let (a, b) = best.corners;
+        svg.push_str(&format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"blue\" fill-opacity=\"0.3\" stroke=\"blue\" stroke-width=\"2\" vector-effect=\"non-scaling-stroke\"/>\n",
+            a.x.min(b.x),
+            a.y.min(b.y),
+            (a.x - b.x).abs() + 1,
+            (a.y - b.y).abs() + 1,
+        ));
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[test]
+fn test_d9_2() {
+    println!("d9_2={}", d9_2(include_str!("day9.txt")));
+}
+
+#[test]
+fn test_d9_floor_svg() {
+    let polygon = polygon::example_floor();
+    let positions = polygon.vertices().to_vec();
+    let best = largest_red_rectangle(&positions, |_| {});
+
+    let svg = floor_svg(&polygon, best.as_ref(), 100);
+    assert!(svg.starts_with(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"100\" height=\"70\" viewBox=\"2 1 10 7\">"
+    ));
+    assert!(svg.contains("<polygon points=\"7.5,1.5 11.5,1.5 11.5,7.5 9.5,7.5 "));
+    assert_eq!(svg.matches("<circle").count(), 8);
+    assert!(svg.contains("<rect x=\"2\" y=\"3\" width=\"8\" height=\"3\" fill=\"blue\""));
+    assert!(svg.ends_with("</svg>\n"));
+
+    let svg = floor_svg(&polygon, None, 100);
+    assert_eq!(svg.matches("<rect").count(), 1);
+}
 
 #[test]
-fn test_d9_1() {
-    println!("d9_1={}", d9_1(include_str!("day9.txt")));
+fn test_d9_equal_areas() {
+    // an L: the two 15-tile arms share their area with two rectangles that
+    // cross the missing corner
+    let positions = parse_red_tiles("0,0\n4,0\n4,2\n2,2\n2,4\n0,4");
+    assert_eq!(d9_1("0,0\n4,0\n4,2\n2,2\n2,4\n0,4"), 25);
+
+    let rectangles = red_rectangles(&positions);
+    assert_eq!(rectangles.len(), 15);
+    let fifteen: Vec<_> = rectangles
+        .iter()
+        .filter(|r| r.area == 15)
+        .map(|r| r.corners)
+        .collect();
+    assert_eq!(
+        fifteen,
+        [
+            (positions[0], positions[2]),
+            (positions[0], positions[4]),
+            (positions[1], positions[4]),
+            (positions[2], positions[5]),
+        ]
+    );
+
+    assert_eq!(
+        largest_red_rectangle(&positions, |_| {}),
+        Some(RedRectangle {
+            area: 15,
+            corners: (Point { x: 0, y: 0 }, Point { x: 4, y: 2 }),
+        })
+    );
 }
 
-pub fn d9_2(input: &str) -> i64 {
-    #[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
-    struct Point {
-        x: i64,
-        y: i64,
+#[test]
+fn test_d9_probes() {
+    let polygon = polygon::example_floor();
+    let positions = polygon.vertices().to_vec();
+    let index = ContainmentIndex::new(&polygon);
+    for (point, inside) in [
+        ((8, 2), true),
+        ((8, 0), false),
+        ((7, 1), true),
+        ((3, 4), true),
+    ] {
+        let point = Point {
+            x: point.0,
+            y: point.1,
+        };
+        assert_eq!(polygon.contains(point), inside, "{point:?}");
+        assert_eq!(index.contains(point), inside, "{point:?}");
     }
 
-    let positions: Vec<Point> = input
-        .lines()
-        .map(|line| {
-            let (xstr, ystr) = line.split_once(',').unwrap();
-            Point {
-                x: xstr.parse::<i64>().unwrap(),
-                y: ystr.parse::<i64>().unwrap(),
-            }
+    let mut events = vec![];
+    assert_eq!(
+        largest_red_rectangle(&positions, |event| events.push(event.clone())).map(|r| r.area),
+        Some(24)
+    );
+    assert_eq!(
+        events.first(),
+        Some(&FloorEvent::BoundingBox {
+            min: Point { x: 2, y: 1 },
+            max: Point { x: 11, y: 7 }
         })
-        .collect();
+    );
+    assert!(matches!(
+        events.last(),
+        Some(FloorEvent::Found(RedRectangle { area: 24, .. }))
+    ));
+    assert!(
+        events[1..events.len() - 1]
+            .iter()
+            .all(|event| matches!(event, FloorEvent::Rejected(r) if r.area >= 24))
+    );
+}
 
-    // Now the rectangles we draw must be contained within the path drawn by the
-    // input...
-    // This is kind of like a concave shape collision detection ?
-    // We basically can't know how concave the shape is, we need to use
-    // the entire path for every check.
-    // The corners of the checked against rectangle will always be contained in the path
-    // but the interior is what matters. Can we "just" check the four corners ? i.e.
-    // for each pair, check whether all four corners are contained inside the path,
-    // if not, go next.
-
-    // First thing we need to implement is a "point_in_path() -> bool" function.
-    // Then it's pretty easy.
-    // For each pair, determine four corners. Check that all corners are inside the
-    // path, if yes, we have a candidate.
-    // Then pick the candidate with greatest area.
-
-    // let positions = [
-    //     (-1, -1),
-    //     (0, -1),
-    //     (1, -1),
-    //     (1, 0),
-    //     (1, 1),
-    //     (0, 1),
-    //     (-1, 1),
-    //     (-1, 0),
-    // ]
-    // .into_iter()
-    // .collect::<Vec<_>>();
-
-    // let positions = [(-3, -3), (3, -3), (3, 3), (-3, 3)]
-    //     .into_iter()
-    //     .collect::<Vec<_>>();
-
-    let mut path = positions.clone();
-    path.push(path[0]); // close the path
-
-    let mut horizontal_edges: HashMap<i64, Vec<(Point, Point)>> = HashMap::new();
-    let mut vertical_edges: HashMap<i64, Vec<(Point, Point)>> = HashMap::new();
-
-    // Create a set of all points in the path
-    let mut path_points: HashSet<Point> = HashSet::new();
-    let mut steps: Vec<(Point, Point)> = vec![];
-    for i in 0..path.len() - 1 {
-        let p1 = path[i];
-        let p2 = path[i + 1];
-        path_points.insert(p1);
-
-        if p1.x == p2.x {
-            let direction = (p2.y - p1.y).signum();
-            vertical_edges.entry(p1.x).or_insert(vec![]).push((p1, p2));
-            // vertical_edges.insert(p1.x, (p1, p2));
-            for j in 0..(p2.y - p1.y).abs() {
-                path_points.insert(Point {
-                    x: p1.x,
-                    y: p1.y + direction * j,
-                });
-                steps.push((
-                    Point {
-                        x: p1.x,
-                        y: p1.y + direction * j,
-                    },
-                    Point { x: 0, y: direction },
-                ));
+pub type ButtonWiring = BitSet;
+pub type Joltage = u64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Machine {
+    /// The lights to turn on.
+    pub lights: BitSet,
+    /// The lights, or joltage counters, each button is wired to.
+    pub buttons: Vec<ButtonWiring>,
+    pub joltages: Vec<Joltage>,
+}
+
+impl Machine {
+    /// Toggles the lights wired to `button`.
+    pub fn press(&self, lights: &mut BitSet, button: usize) {
+        *lights ^= &self.buttons[button];
+    }
+}
+
+pub fn parse_machines(input: &str) -> Vec<Machine> {
+    input.lines().fold(vec![], |mut m, line: &str| {
+        let (mut l, mut b, mut j) = (vec![], vec![], vec![]);
+        line.split_ascii_whitespace().for_each(|item| {
+            match item.chars().next().unwrap() {
+                '[' => {
+                    // lights
+                    for c in item
+                        .strip_prefix('[')
+                        .unwrap()
+                        .strip_suffix(']')
+                        .unwrap()
+                        .chars()
+                    {
+                        l.push(match c {
+                            '.' => false,
+                            '#' => true,
+                            _ => panic!(),
+                        });
+                    }
+                }
+                '(' => {
+                    let stripped = item.strip_prefix('(').unwrap().strip_suffix(')').unwrap();
+                    let mut targets = vec![];
+                    for val_str in stripped.split(',') {
+                        targets.push(val_str.parse().unwrap());
+                    }
+                    b.push(targets);
+                }
+                '{' => {
+                    let stripped = item.strip_prefix('{').unwrap().strip_suffix('}').unwrap();
+                    for val_str in stripped.split(',') {
+                        j.push(val_str.parse::<Joltage>().unwrap());
+                    }
+                }
+                _ => panic!(),
+            };
+        });
+        assert_eq!(l.len(), j.len());
+        m.push(Machine {
+            lights: BitSet::from_indices(l.len(), (0..l.len()).filter(|&i| l[i])),
+            buttons: b
+                .into_iter()
+                .map(|targets| BitSet::from_indices(l.len(), targets))
+                .collect(),
+            joltages: j,
+        });
+        m
+    })
+}
+
+/// Why [`light_presses`] found no presses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightsError {
+    /// No combination of buttons turns on exactly the goal lights.
+    Unreachable,
+    /// Both ways of finding the fewest presses would take too long: there are
+    /// `free` free buttons and the buttons reach `2^rank` light patterns.
+    TooLarge { free: usize, rank: usize },
+}
+
+impl std::fmt::Display for LightsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LightsError::Unreachable => f.write_str("unreachable"),
+            LightsError::TooLarge { free, rank } => {
+                write!(f, "too large: {free} free buttons, rank {rank}")
             }
-        } else if p1.y == p2.y {
-            let direction = (p2.x - p1.x).signum();
-            horizontal_edges
-                .entry(p1.y)
-                .or_insert(vec![])
-                .push((p1, p2));
-            for j in 0..(p2.x - p1.x).abs() {
-                path_points.insert(Point {
-                    x: p1.x + direction * j,
-                    y: p1.y,
-                });
-                steps.push((
-                    Point {
-                        x: p1.x + direction * j,
-                        y: p1.y,
-                    },
-                    Point { x: direction, y: 0 },
-                ));
+        }
+    }
+}
+
+/// Going through the `2^k` combinations of free buttons is given up past this.
+const MAX_FREE_BUTTONS: usize = 30;
+/// Searching the `2^r` reachable light patterns is given up past this.
+const MAX_SEARCH_RANK: usize = 22;
+
+/// The lights equations of a machine in reduced row echelon form over GF(2).
+struct LightSystem {
+    /// One row per pivot button, with the goal as the last column.
+    rows: Vec<BitSet>,
+    pivots: Vec<usize>,
+    columns: usize,
+}
+
+impl LightSystem {
+    fn new(goal: &BitSet, buttons: &[ButtonWiring]) -> Result<LightSystem, LightsError> {
+        let columns = buttons.len();
+
+        // one equation per light, with the goal as the last column
+        let mut rows: Vec<BitSet> = (0..goal.len())
+            .map(|light| {
+                let mut row = BitSet::new(columns + 1);
+                row.set(columns, goal.get(light));
+                row
+            })
+            .collect();
+        for (b, wiring) in buttons.iter().enumerate() {
+            for light in wiring.ones() {
+                rows[light].flip(b);
             }
-        } else {
-            panic!();
         }
+
+        let mut pivots: Vec<usize> = vec![];
+        for column in 0..columns {
+            let rank = pivots.len();
+            let Some(pivot) = (rank..rows.len()).find(|&r| rows[r].get(column)) else {
+                continue;
+            };
+            rows.swap(rank, pivot);
+            let pivot_row = rows[rank].clone();
+            for (r, row) in rows.iter_mut().enumerate() {
+                if r != rank && row.get(column) {
+                    *row ^= &pivot_row;
+                }
+            }
+            pivots.push(column);
+        }
+        if rows[pivots.len()..].iter().any(|row| row.get(columns)) {
+            // 0 = 1
+            return Err(LightsError::Unreachable);
+        }
+        rows.truncate(pivots.len());
+
+        Ok(LightSystem {
+            rows,
+            pivots,
+            columns,
+        })
     }
-    // dbg!(&path_points);
-    // dbg!(&steps);
 
-    #[derive(Debug)]
-    struct Rect {
-        topleft: Point,
-        dims: Point,
-        bottomright: Point,
+    fn free(&self) -> usize {
+        self.columns - self.pivots.len()
     }
-    #[derive(Debug)]
-    struct Floor {
-        bounding_rect: Rect,
+
+    fn rank(&self) -> usize {
+        self.pivots.len()
     }
 
-    let min_x = path_points
-        .iter()
-        .fold(i64::max_value(), |acc, point| point.x.min(acc));
-    let min_y = path_points
-        .iter()
-        .fold(i64::max_value(), |acc, point| point.y.min(acc));
-    let max_x = path_points
-        .iter()
-        .fold(i64::min_value(), |acc, point| point.x.max(acc));
-    let max_y = path_points
-        .iter()
-        .fold(i64::min_value(), |acc, point| point.y.max(acc));
-    dbg!(min_x, min_y, max_x, max_y);
-
-    let floor = Floor {
-        bounding_rect: Rect {
-            topleft: Point { x: min_x, y: min_y },
-            dims: Point {
-                x: max_x - min_x,
-                y: max_y - min_y,
-            },
-            bottomright: Point { x: max_x, y: max_y },
-        },
-    };
+    /// Whether searching the `2^r` reachable patterns, `columns` buttons at a
+    /// time, beats going through the `2^k` combinations of free buttons.
+    fn prefers_search(&self) -> bool {
+        let log_columns = self.columns.next_power_of_two().trailing_zeros() as usize;
+        self.free() > self.rank() + log_columns + 2
+    }
 
-    // dbg!(floor);
-
-    // let mut points_inside_path: HashSet<Point> = HashSet::with_capacity(100000000);
-
-    // for row in floor.bounding_rect.topleft.y..floor.bounding_rect.bottomright.y {
-    //     dbg!((
-    //         row,
-    //         floor.bounding_rect.bottomright.y,
-    //         points_inside_path.len()
-    //     ));
-    //     let mut inside_path = false;
-    //     for col in floor.bounding_rect.topleft.x..floor.bounding_rect.bottomright.x {
-    //         let p = Point { x: col, y: row };
-    //         if inside_path {
-    //             // dbg!(p);
-    //             points_inside_path.insert(p);
-    //         } else if path_points.contains(&p) {
-    //             // dbg!(p);
-    //             points_inside_path.insert(p);
-    //             inside_path = !inside_path;
-    //         } else {
-    //             // dbg!(("else", p));
-    //         }
-    //     }
-    // }
-
-    fn is_inside_path(
-        point: &Point,
-        path_points: &HashSet<Point>,
-        bounding_rect: &Rect,
-        horizontal_edges: &HashMap<i64, Vec<(Point, Point)>>,
-        memo: &mut HashMap<Point, bool>,
-    ) -> bool {
-        if let Some(ret) = memo.get(point) {
-            return *ret;
-        }
-        if path_points.contains(point) {
-            return true;
-        }
-
-        let mut intersections = 0;
-        // ray cast vertically down
-        for j in (bounding_rect.topleft.y..=point.y - 1).rev() {
-            if let Some(edges) = horizontal_edges.get(&j) {
-                // dbg!((j, edges));
-                for edge in edges {
-                    let left = edge.0.x.min(edge.1.x);
-                    let right = edge.0.x.max(edge.1.x);
-                    if left < point.x && right >= point.x {
-                        intersections += 1;
-                    }
+    /// Every solution is a particular one plus a combination of a basis of
+    /// the null space, one vector per free button: walk the combinations in
+    /// Gray code order, changing one basis vector at a time.
+    fn by_gray_code(&self) -> Vec<usize> {
+        let columns = self.columns;
+        let mut solution = BitSet::new(columns);
+        for (row, &column) in self.rows.iter().zip(&self.pivots) {
+            solution.set(column, row.get(columns));
+        }
+        let basis: Vec<BitSet> = (0..columns)
+            .filter(|column| !self.pivots.contains(column))
+            .map(|free| {
+                let mut vector = BitSet::new(columns);
+                vector.set(free, true);
+                for (row, &column) in self.rows.iter().zip(&self.pivots) {
+                    vector.set(column, row.get(free));
                 }
-                // dbg!((j, edges, intersections));
+                vector
+            })
+            .collect();
+
+        let mut fewest = solution.clone();
+        for i in 1u64..1 << basis.len() {
+            solution ^= &basis[i.trailing_zeros() as usize];
+            if solution.count_ones() < fewest.count_ones() {
+                fewest = solution.clone();
             }
         }
-        if intersections % 2 == 1 {
-            memo.insert(point.clone(), true);
-            return true;
-        } else {
-            memo.insert(point.clone(), false);
-            return false;
+        (0..columns).map(|b| fewest.get(b) as usize).collect()
+    }
+
+    /// Breadth-first search from all lights off to the goal. The reachable
+    /// patterns are combinations of the pivot buttons' columns, so each is
+    /// stored as its `r` coefficients in a `u64`: button `b` is `rows[i][b]`
+    /// for each row `i`, and the goal is the last column. A shortest path
+    /// never presses a button twice, as the second press would undo the
+    /// first.
+    fn by_search(&self) -> Vec<usize> {
+        let columns = self.columns;
+        let coordinates = |column: usize| {
+            self.rows
+                .iter()
+                .enumerate()
+                .fold(0u64, |bits, (i, row)| bits | (row.get(column) as u64) << i)
+        };
+        let buttons: Vec<u64> = (0..columns).map(coordinates).collect();
+        let goal = coordinates(columns);
+
+        // the button pressed to reach each pattern
+        const UNSEEN: u32 = u32::MAX;
+        let mut previous = vec![UNSEEN; 1 << self.rank()];
+        previous[0] = 0;
+        let mut queue = VecDeque::from([0u64]);
+        while let Some(lights) = queue.pop_front() {
+            if lights == goal {
+                break;
+            }
+            for (button, &wiring) in buttons.iter().enumerate() {
+                let next = lights ^ wiring;
+                if previous[next as usize] == UNSEEN {
+                    previous[next as usize] = button as u32;
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        let mut presses = vec![0; columns];
+        let mut lights = goal;
+        while lights != 0 {
+            let button = previous[lights as usize] as usize;
+            presses[button] += 1;
+            lights ^= buttons[button];
         }
+        presses
     }
+}
 
-    let mut memo = HashMap::new();
+/// How many times to press each button, at most once, to turn the lights
+/// from all off to `goal` with the fewest presses.
+///
+/// Pressing a button twice changes nothing, so this is the solution of
+/// `A·x = goal` over GF(2) with the fewest ones, where column `b` of `A` is
+/// the lights toggled by button `b`. Gaussian elimination gives one solution
+/// and a basis of the null space, and every other solution is that one plus
+/// a combination of the basis: with `k` free buttons, there are `2^k` of them
+/// to go through. The buttons only reach `2^r` light patterns though, `r`
+/// being the rank of `A`, so when `2^k` is well over `2^r` times the number
+/// of buttons a breadth-first search over the patterns is quicker.
+///
+/// Finding the fewest presses is hard in general, so this gives up with
+/// [`LightsError::TooLarge`] when there are more than 30 free buttons and
+/// the rank is over 22.
+pub fn light_presses(goal: &BitSet, buttons: &[ButtonWiring]) -> Result<Vec<usize>, LightsError> {
+    let system = LightSystem::new(goal, buttons)?;
+    let searchable = system.rank() <= MAX_SEARCH_RANK;
+    if searchable && (system.prefers_search() || system.free() > MAX_FREE_BUTTONS) {
+        Ok(system.by_search())
+    } else if system.free() <= MAX_FREE_BUTTONS {
+        Ok(system.by_gray_code())
+    } else {
+        Err(LightsError::TooLarge {
+            free: system.free(),
+            rank: system.rank(),
+        })
+    }
+}
 
-    dbg!(is_inside_path(
-        &Point { x: 8, y: 2 },
-        &path_points,
-        &floor.bounding_rect,
-        &horizontal_edges,
-        &mut memo,
-    ));
+pub fn fewest_light_presses(goal: &BitSet, buttons: &[ButtonWiring]) -> Result<usize, LightsError> {
+    light_presses(goal, buttons).map(|presses| presses.iter().sum())
+}
 
-    dbg!(is_inside_path(
-        &Point { x: 8, y: 0 },
-        &path_points,
-        &floor.bounding_rect,
-        &horizontal_edges,
-        &mut memo,
-    ));
-    dbg!(is_inside_path(
-        &Point { x: 7, y: 1 },
-        &path_points,
-        &floor.bounding_rect,
-        &horizontal_edges,
-        &mut memo,
-    ));
+pub fn d10_1(input: &str) -> usize {
+    configure_lights(&parse_machines(input))
+        .into_iter()
+        .map(|solution| solution.unwrap().total())
+        .sum()
+}
 
-    dbg!(is_inside_path(
-        &Point { x: 3, y: 4 },
-        &path_points,
-        &floor.bounding_rect,
-        &horizontal_edges,
-        &mut memo,
-    ));
-    let bounding_rect = &floor.bounding_rect;
-
-    let mut top_area = 0;
-    // // For each pair of corners, find the one whose corners all lie inside the path, and
-    // // has with highest area.
-    // Instead of checking all pairs, check the most far away ones first.
-    // The first one we find that's entirely in the path is the answer.
-    // -> sort pairs of positions by the area they describe
-    let mut pairs: BTreeMap<i64, (Point, Point)> = BTreeMap::new();
-    for p1 in &positions {
-        for p2 in &positions {
-            if p1 == p2 {
-                continue;
+#[test]
+fn test_d10_1() {
+    println!("d10_1={}", d10_1(include_str!("day10.txt")));
+}
+
+#[test]
+fn test_d10_light_presses() {
+    let mut machines = parse_machines(include_str!("day10.txt"));
+    machines.extend(parse_machines("[##] (0) {1,2}"));
+    let presses: Vec<_> = machines
+        .iter()
+        .map(|machine| fewest_light_presses(&machine.lights, &machine.buttons))
+        .collect();
+    assert_eq!(
+        presses,
+        [Ok(2), Ok(3), Ok(2), Err(LightsError::Unreachable)]
+    );
+
+    let mut lights = BitSet::new(4);
+    machines[0].press(&mut lights, 4);
+    machines[0].press(&mut lights, 5);
+    assert_eq!(lights.to_string(), ".##.");
+    assert_eq!(lights, machines[0].lights);
+
+    // more buttons than fit in a word: one per light, and one for all of them
+    let mut buttons: Vec<ButtonWiring> = (0..70)
+        .map(|light| BitSet::from_indices(70, [light]))
+        .collect();
+    buttons.push(BitSet::from_indices(70, 0..70));
+    let goal =
+        |on: &dyn Fn(usize) -> bool| BitSet::from_indices(70, (0..70).filter(|&light| on(light)));
+    assert_eq!(fewest_light_presses(&goal(&|_| true), &buttons), Ok(1));
+    assert_eq!(
+        fewest_light_presses(&goal(&|light| light < 3), &buttons),
+        Ok(3)
+    );
+    assert_eq!(
+        fewest_light_presses(&goal(&|light| light != 65), &buttons),
+        Ok(2)
+    );
+    assert_eq!(fewest_light_presses(&goal(&|_| false), &buttons), Ok(0));
+
+    // far more free buttons than lights: 65 for the first light, and one
+    // for both
+    let mut buttons: Vec<ButtonWiring> = (0..65).map(|_| BitSet::from_indices(2, [0])).collect();
+    buttons.push(BitSet::from_indices(2, [0, 1]));
+    let presses = light_presses(&BitSet::from_indices(2, [0, 1]), &buttons).unwrap();
+    assert_eq!(presses.iter().sum::<usize>(), 1);
+    assert_eq!(presses[65], 1);
+    assert_eq!(
+        fewest_light_presses(&BitSet::from_indices(2, [1]), &buttons),
+        Ok(2)
+    );
+    assert_eq!(
+        fewest_light_presses(&BitSet::from_indices(2, []), &buttons),
+        Ok(0)
+    );
+
+    // around the point where searching the patterns gets cheaper: 6 lights
+    // and pseudo-random buttons, both ways must find as few presses
+    let mut seed = 12345u64;
+    let mut random = move || {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (seed >> 33) as usize
+    };
+    for count in 14..24 {
+        let mut buttons: Vec<ButtonWiring> = (0..6)
+            .map(|light| BitSet::from_indices(6, [light]))
+            .collect();
+        buttons.extend(
+            (6..count).map(|_| BitSet::from_indices(6, (0..6).filter(|_| random() % 2 == 0))),
+        );
+        let goal = BitSet::from_indices(6, (0..6).filter(|_| random() % 2 == 0));
+        let system = LightSystem::new(&goal, &buttons).unwrap();
+        // with rank 6 and 17 to 23 buttons, searching wins past 13 free ones
+        assert_eq!(system.prefers_search(), count - 6 > 13);
+        let by_gray_code = system.by_gray_code();
+        let by_search = system.by_search();
+        assert_eq!(
+            by_gray_code.iter().sum::<usize>(),
+            by_search.iter().sum::<usize>()
+        );
+        for presses in [by_gray_code, by_search] {
+            let mut lights = BitSet::new(6);
+            for (button, _) in presses.iter().enumerate().filter(|&(_, &count)| count == 1) {
+                lights ^= &buttons[button];
             }
-            let area = ((p1.x - p2.x).abs() + 1) * ((p1.y - p2.y).abs() + 1);
-            pairs.insert(area, (*p1, *p2));
+            assert_eq!(lights, goal);
         }
     }
 
-    'pair_loop: for (area, (p1, p2)) in pairs.iter().rev() {
-        let topleft = Point {
-            x: p1.x.min(p2.x),
-            y: p1.y.min(p2.y),
-        };
-        let topright = Point {
-            x: p1.x.max(p2.x),
-            y: p1.y.min(p2.y),
-        };
-        let bottomright = Point {
-            x: p1.x.max(p2.x),
-            y: p1.y.max(p2.y),
-        };
-        let bottomleft = Point {
-            x: p1.x.min(p2.x),
-            y: p1.y.max(p2.y),
+    // 64 free buttons on 64 lights of full rank: too many combinations and
+    // too many patterns
+    let mut buttons: Vec<ButtonWiring> = (0..64)
+        .map(|light| BitSet::from_indices(64, [light]))
+        .collect();
+    buttons.extend((0..64).map(|light| BitSet::from_indices(64, [light, (light + 1) % 64])));
+    assert_eq!(
+        light_presses(&BitSet::from_indices(64, [0]), &buttons),
+        Err(LightsError::TooLarge { free: 64, rank: 64 })
+    );
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 { a.abs() } else { gcd(b, a % b) }
+}
+
+/// How many times to press each button to raise the joltage counters from
+/// zero to exactly `targets` with the fewest presses, or `None` if no
+/// combination of presses does.
+///
+/// This is the integer solution of `A·x = targets, x ≥ 0` with the smallest
+/// sum, where column `b` of `A` is the counters incremented by button `b`.
+/// Fraction-free Gaussian elimination expresses each pivot button in terms
+/// of the free ones, which are then searched depth first. A free button is
+/// pressed at most as often as the smallest target among its counters, and
+/// no more than keeps the pivot buttons of the rows it settles at zero
+/// presses or more. The total is then linear in the free presses, which
+/// bounds what the rest of the search can still save.
+pub fn joltage_presses(targets: &[Joltage], buttons: &[ButtonWiring]) -> Option<Vec<usize>> {
+    let columns = buttons.len();
+    let mut rows: Vec<Vec<i64>> = targets
+        .iter()
+        .map(|&target| {
+            let mut row = vec![0; columns + 1];
+            row[columns] = target as i64;
+            row
+        })
+        .collect();
+    for (b, wiring) in buttons.iter().enumerate() {
+        for counter in wiring.ones() {
+            rows[counter][b] = 1;
+        }
+    }
+    // reduced row echelon form, keeping every entry an integer: instead of
+    // dividing by the pivot, scale the other rows up by it
+    let mut pivots: Vec<usize> = vec![];
+    for column in 0..columns {
+        let rank = pivots.len();
+        let Some(pivot) = (rank..rows.len()).find(|&r| rows[r][column] != 0) else {
+            continue;
         };
-        if is_inside_path(
-            &topleft,
-            &path_points,
-            bounding_rect,
-            &horizontal_edges,
-            &mut memo,
-        ) && is_inside_path(
-            &topright,
-            &path_points,
-            bounding_rect,
-            &horizontal_edges,
-            &mut memo,
-        ) && is_inside_path(
-            &bottomright,
-            &path_points,
-            bounding_rect,
-            &horizontal_edges,
-            &mut memo,
-        ) && is_inside_path(
-            &bottomleft,
-            &path_points,
-            bounding_rect,
-            &horizontal_edges,
-            &mut memo,
-        ) {
-            // Now check all points...
-            // maybe all points is overkill, and the boundary is sufficient
-            // yes that sounds reasonable -> just check the perimeter
-            println!("checking all points for {:?} {:?}", p1, p2);
-            // top and bottom edges
-            for i in topleft.x + 1..topright.x {
-                if i % 1000 == 0 {
-                    println!("i={}", i);
-                }
-                if !is_inside_path(
-                    &Point { x: i, y: topleft.y },
-                    &path_points,
-                    bounding_rect,
-                    &horizontal_edges,
-                    &mut memo,
-                ) || !is_inside_path(
-                    &Point {
-                        x: i,
-                        y: bottomleft.y,
-                    },
-                    &path_points,
-                    bounding_rect,
-                    &horizontal_edges,
-                    &mut memo,
-                ) {
-                    continue 'pair_loop;
+        rows.swap(rank, pivot);
+        let pivot_row = rows[rank].clone();
+        for (r, row) in rows.iter_mut().enumerate() {
+            let factor = row[column];
+            if r == rank || factor == 0 {
+                continue;
+            }
+            for (entry, pivot_entry) in row.iter_mut().zip(&pivot_row) {
+                *entry = *entry * pivot_row[column] - pivot_entry * factor;
+            }
+            let divisor = row.iter().fold(0, |g, &entry| gcd(g, entry));
+            if divisor > 1 {
+                row.iter_mut().for_each(|entry| *entry /= divisor);
+            }
+        }
+        pivots.push(column);
+    }
+    if rows[pivots.len()..].iter().any(|row| row[columns] != 0) {
+        return None;
+    }
+    rows.truncate(pivots.len());
+    // with positive pivots, a pivot button is pressed at least zero times
+    // when the free part of its row is at most the target
+    for (row, &pivot) in rows.iter_mut().zip(&pivots) {
+        if row[pivot] < 0 {
+            row.iter_mut().for_each(|entry| *entry = -*entry);
+        }
+    }
+    let free: Vec<usize> = (0..columns).filter(|c| !pivots.contains(c)).collect();
+
+    // rows that only depend on the first `k` free buttons, in `settled[k]`,
+    // can be checked as soon as those are pressed
+    let mut settled = vec![vec![]; free.len() + 1];
+    for (r, row) in rows.iter().enumerate() {
+        let k = free.iter().rposition(|&f| row[f] != 0).map_or(0, |k| k + 1);
+        settled[k].push(r);
+    }
+
+    // Each row gives `x[pivot] = (target - Σ row[f] · x[f]) / pivot`, so the
+    // total is `Σ target / pivot` plus a weight per free press.
+    let constant: f64 = rows
+        .iter()
+        .zip(&pivots)
+        .map(|(row, &pivot)| row[columns] as f64 / row[pivot] as f64)
+        .sum();
+    let weights: Vec<f64> = free
+        .iter()
+        .map(|&f| {
+            1.0 - rows
+                .iter()
+                .zip(&pivots)
+                .map(|(row, &pivot)| row[f] as f64 / row[pivot] as f64)
+                .sum::<f64>()
+        })
+        .collect();
+
+    // Presses of the free buttons so far, and what is left of each target
+    // for the other buttons. Those only add to counters, so no leftover can
+    // be negative, and they need at least as many presses as the largest.
+    struct Search<'a> {
+        rows: &'a [Vec<i64>],
+        pivots: &'a [usize],
+        free: &'a [usize],
+        settled: &'a [Vec<usize>],
+        buttons: &'a [ButtonWiring],
+        constant: f64,
+        weights: &'a [f64],
+        presses: Vec<i64>,
+        leftover: Vec<i64>,
+        best: Option<i64>,
+        best_presses: Vec<usize>,
+    }
+
+    impl Search<'_> {
+        /// What is left of the target of row `r` once the free buttons
+        /// pressed so far are taken off.
+        fn remainder(&self, r: usize) -> i64 {
+            let row = &self.rows[r];
+            let rest: i64 = self
+                .free
+                .iter()
+                .zip(&self.presses)
+                .map(|(&f, &x)| row[f] * x)
+                .sum();
+            row[self.buttons.len()] - rest
+        }
+
+        /// Presses of the pivot button of row `r`, if they are a whole,
+        /// non-negative number. Row `r` is
+        /// `pivot · x[pivot] + Σ row[f] · x[f] = target`.
+        fn pivot_presses(&self, r: usize) -> Option<i64> {
+            let numerator = self.remainder(r);
+            let pivot = self.rows[r][self.pivots[r]];
+            (numerator % pivot == 0 && numerator >= 0).then_some(numerator / pivot)
+        }
+
+        /// The most a free button can be pressed without taking a counter
+        /// past its target.
+        fn most_presses(&self, button: usize) -> i64 {
+            self.buttons[button]
+                .ones()
+                .map(|c| self.leftover[c])
+                .min()
+                .unwrap_or(0)
+        }
+
+        /// The presses of the `k`th free button that keep the pivot buttons
+        /// of the rows it settles at zero presses or more.
+        fn range(&self, k: usize) -> (i64, i64) {
+            let mut low = 0;
+            let mut high = self.most_presses(self.free[k]);
+            for &r in &self.settled[k + 1] {
+                let coefficient = self.rows[r][self.free[k]];
+                let remainder = self.remainder(r);
+                if coefficient > 0 {
+                    high = high.min(remainder.div_euclid(coefficient));
+                } else {
+                    low = low.max(-(remainder.div_euclid(-coefficient)));
                 }
             }
+            (low, high)
+        }
+
+        /// The fewest total presses any solution below this point can have.
+        fn lower_bound(&self) -> i64 {
+            let k = self.presses.len();
+            let pressed: f64 = self
+                .weights
+                .iter()
+                .zip(&self.presses)
+                .map(|(&w, &x)| w * x as f64)
+                .sum();
+            // unpressed buttons with a negative weight could lower the total
+            // by as much as their most presses
+            let savings: f64 = self.free[k..]
+                .iter()
+                .zip(&self.weights[k..])
+                .filter(|&(_, &w)| w < 0.0)
+                .map(|(&f, &w)| w * self.most_presses(f) as f64)
+                .sum();
+            let linear = (self.constant + pressed + savings - 1e-6).ceil() as i64;
+
+            let needed = self.leftover.iter().copied().max().unwrap_or(0);
+            linear.max(self.presses.iter().sum::<i64>() + needed)
+        }
+
+        /// Presses the last free button pressed so far `count` times instead.
+        fn repress(&mut self, count: i64) {
+            let k = self.presses.len() - 1;
+            let change = count - self.presses[k];
+            self.buttons[self.free[k]]
+                .ones()
+                .for_each(|c| self.leftover[c] -= change);
+            self.presses[k] = count;
+        }
+
+        fn run(&mut self) {
+            let k = self.presses.len();
+            if self.settled[k]
+                .iter()
+                .any(|&r| self.pivot_presses(r).is_none())
+            {
+                return;
+            }
+            if self.best.is_some_and(|best| self.lower_bound() >= best) {
+                return;
+            }
 
-            // left and right edges
-            for j in topleft.y + 1..bottomleft.y {
-                if j % 1000 == 0 {
-                    println!("j={}", j);
+            if k < self.free.len() {
+                // more presses lower the total when the weight is negative,
+                // try those first to find a good solution early
+                let (low, high) = self.range(k);
+                let mut counts: Vec<i64> = (low..=high).collect();
+                if self.weights[k] < 0.0 {
+                    counts.reverse();
                 }
-                if !is_inside_path(
-                    &Point { x: topleft.x, y: j },
-                    &path_points,
-                    bounding_rect,
-                    &horizontal_edges,
-                    &mut memo,
-                ) || !is_inside_path(
-                    &Point {
-                        x: topright.x,
-                        y: j,
-                    },
-                    &path_points,
-                    bounding_rect,
-                    &horizontal_edges,
-                    &mut memo,
-                ) {
-                    continue 'pair_loop;
+                self.presses.push(0);
+                for count in counts {
+                    self.repress(count);
+                    self.run();
                 }
+                self.repress(0);
+                self.presses.pop();
+                return;
+            }
+
+            let mut all_presses = vec![0; self.buttons.len()];
+            for (&f, &x) in self.free.iter().zip(&self.presses) {
+                all_presses[f] = x as usize;
+            }
+            for (r, &pivot) in self.pivots.iter().enumerate() {
+                all_presses[pivot] = self.pivot_presses(r).unwrap() as usize;
+            }
+            let total = all_presses.iter().sum::<usize>() as i64;
+            if self.best.is_none_or(|best| total < best) {
+                self.best = Some(total);
+                self.best_presses = all_presses;
             }
-            // if we get there, our whole perimeter is in the path
-            dbg!((p1, p2, area));
-            return *area;
         }
     }
 
-    top_area
+    let mut search = Search {
+        rows: &rows,
+        pivots: &pivots,
+        free: &free,
+        settled: &settled,
+        buttons,
+        constant,
+        weights: &weights,
+        presses: vec![],
+        leftover: targets.iter().map(|&target| target as i64).collect(),
+        best: None,
+        best_presses: vec![],
+    };
+    search.run();
+    search.best.map(|_| search.best_presses)
 }
 
-#[test]
-fn test_d9_2() {
-    println!("d9_2={}", d9_2(include_str!("day9.txt")));
+pub fn fewest_joltage_presses(targets: &[Joltage], buttons: &[ButtonWiring]) -> Option<usize> {
+    joltage_presses(targets, buttons).map(|presses| presses.iter().sum())
 }
 
-pub fn d10_1(input: &str) -> usize {
-    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-    enum LightState {
-        On,
-        Off,
-    }
-    type ButtonWiring = Vec<usize>;
-    type Joltage = u64;
-
-    let machines: Vec<(Vec<LightState>, Vec<ButtonWiring>, Vec<Joltage>)> =
-        input.lines().fold(vec![], |mut m, line: &str| {
-            let (mut l, mut b, mut j) = (vec![], vec![], vec![]);
-            line.split_ascii_whitespace().for_each(|item| {
-                match item.chars().nth(0).unwrap() {
-                    '[' => {
-                        // lights
-                        for c in item
-                            .strip_prefix('[')
-                            .unwrap()
-                            .strip_suffix(']')
-                            .unwrap()
-                            .chars()
-                        {
-                            l.push(match c {
-                                '.' => LightState::Off,
-                                '#' => LightState::On,
-                                _ => panic!(),
-                            });
-                        }
-                    }
-                    '(' => {
-                        let stripped = item.strip_prefix('(').unwrap().strip_suffix(')').unwrap();
-                        let mut targets = vec![];
-                        for val_str in stripped.split(',') {
-                            targets.push(val_str.parse().unwrap());
-                        }
-                        b.push(targets);
-                    }
-                    '{' => {
-                        // not needed yet.
-                        let stripped = item.strip_prefix('{').unwrap().strip_suffix('}').unwrap();
-                        for val_str in stripped.split(',') {
-                            j.push(val_str.parse::<Joltage>().unwrap());
-                        }
-                    }
-                    _ => panic!(),
-                };
-            });
-            assert_eq!(l.len(), j.len());
-            m.push((l, b, j));
-            return m;
-        });
+/// Instructions for configuring one machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MachineSolution {
+    /// Position of the machine in the input.
+    pub machine: usize,
+    /// How many times to press each button.
+    pub presses: Vec<usize>,
+}
 
-    // dbg!(&machines);
-
-    fn get_fewest_presses(goal: &[LightState], wiring: &[ButtonWiring]) -> usize {
-        // this is a bfs
-        let num_buttons = wiring.len();
-        let mut q = VecDeque::new();
-        let mut visited: HashSet<Vec<LightState>> = HashSet::new();
-        q.push_back((0, vec![LightState::Off; goal.len()]));
-
-        loop {
-            if let Some((num_presses, state)) = q.pop_front() {
-                // dbg!(button, &wiring);
-                // dbg!(button, &state);
-                if goal.iter().zip(&state).all(|(x, y)| *x == *y) {
-                    // stop here, we have reached the goal state.
-                    return num_presses;
-                } else {
-                    for i in 0..num_buttons {
-                        let mut new_state = state.clone();
-                        for light_idx in &wiring[i] {
-                            new_state[*light_idx] = match state[*light_idx] {
-                                LightState::On => LightState::Off,
-                                LightState::Off => LightState::On,
-                            }
-                        }
-                        if !visited.contains(&new_state) {
-                            visited.insert(new_state.clone());
-                            q.push_back((num_presses + 1, new_state));
-                        } else {
-                            // println!("visited {:?}", new_state);
-                        }
-                    }
-                }
-            } else {
-                panic!();
-            }
+impl MachineSolution {
+    pub fn total(&self) -> usize {
+        self.presses.iter().sum()
+    }
+
+    /// The buttons to press one after the other. Order doesn't matter, so
+    /// this goes through them in increasing order.
+    pub fn sequence(&self) -> Vec<usize> {
+        self.presses
+            .iter()
+            .enumerate()
+            .flat_map(|(button, &count)| std::iter::repeat_n(button, count))
+            .collect()
+    }
+
+    /// Its machine, if it exists and has a button for each press count.
+    fn machine_in<'a>(&self, machines: &'a [Machine]) -> Option<&'a Machine> {
+        machines
+            .get(self.machine)
+            .filter(|machine| machine.buttons.len() == self.presses.len())
+    }
+
+    /// Whether replaying the presses on its machine turns on exactly the
+    /// lights it should.
+    pub fn validate_lights(&self, machines: &[Machine]) -> bool {
+        let Some(machine) = self.machine_in(machines) else {
+            return false;
+        };
+        let mut lights = BitSet::new(machine.lights.len());
+        for button in self.sequence() {
+            machine.press(&mut lights, button);
         }
+        lights == machine.lights
     }
 
-    let mut total_presses = 0;
-    for (l, b, _) in &machines {
-        let num_presses = get_fewest_presses(l, b);
-        // dbg!((l, num_presses));
-        total_presses += num_presses;
+    /// Whether replaying the presses on its machine raises each joltage
+    /// counter to exactly its target.
+    pub fn validate_joltages(&self, machines: &[Machine]) -> bool {
+        let Some(machine) = self.machine_in(machines) else {
+            return false;
+        };
+        let mut counters = vec![0; machine.joltages.len()];
+        for button in self.sequence() {
+            for counter in machine.buttons[button].ones() {
+                counters[counter] += 1;
+            }
+        }
+        counters == machine.joltages
     }
+}
 
-    total_presses
+/// Fewest presses to turn on each machine's lights, or why they couldn't be
+/// found.
+pub fn configure_lights(machines: &[Machine]) -> Vec<Result<MachineSolution, LightsError>> {
+    machines
+        .iter()
+        .enumerate()
+        .map(|(index, machine)| {
+            light_presses(&machine.lights, &machine.buttons).map(|presses| MachineSolution {
+                machine: index,
+                presses,
+            })
+        })
+        .collect()
 }
 
-#[test]
-fn test_d10_1() {
-    println!("d10_1={}", d10_1(include_str!("day10.txt")));
+/// Fewest presses to set each machine's joltage counters, `None` for the
+/// machines where that is impossible.
+pub fn configure_joltages(machines: &[Machine]) -> Vec<Option<MachineSolution>> {
+    machines
+        .iter()
+        .enumerate()
+        .map(|(index, machine)| {
+            joltage_presses(&machine.joltages, &machine.buttons).map(|presses| MachineSolution {
+                machine: index,
+                presses,
+            })
+        })
+        .collect()
 }
 
 pub fn d10_2(input: &str) -> usize {
-    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-    enum LightState {
-        On,
-        Off,
-    }
-    type ButtonWiring = Vec<usize>;
-    type Joltage = u64;
-
-    let machines: Vec<(Vec<LightState>, Vec<ButtonWiring>, Vec<Joltage>)> =
-        input.lines().fold(vec![], |mut m, line: &str| {
-            let (mut l, mut b, mut j) = (vec![], vec![], vec![]);
-            line.split_ascii_whitespace().for_each(|item| {
-                match item.chars().nth(0).unwrap() {
-                    '[' => {
-                        // lights
-                        for c in item
-                            .strip_prefix('[')
-                            .unwrap()
-                            .strip_suffix(']')
-                            .unwrap()
-                            .chars()
-                        {
-                            l.push(match c {
-                                '.' => LightState::Off,
-                                '#' => LightState::On,
-                                _ => panic!(),
-                            });
-                        }
-                    }
-                    '(' => {
-                        let stripped = item.strip_prefix('(').unwrap().strip_suffix(')').unwrap();
-                        let mut targets = vec![];
-                        for val_str in stripped.split(',') {
-                            targets.push(val_str.parse().unwrap());
-                        }
-                        b.push(targets);
-                    }
-                    '{' => {
-                        // not needed yet.
-                        let stripped = item.strip_prefix('{').unwrap().strip_suffix('}').unwrap();
-                        for val_str in stripped.split(',') {
-                            j.push(val_str.parse::<Joltage>().unwrap());
-                        }
-                    }
-                    _ => panic!(),
-                };
-            });
-            assert_eq!(l.len(), j.len());
-            m.push((l, b, j));
-            return m;
-        });
+    configure_joltages(&parse_machines(input))
+        .into_iter()
+        .map(|solution| solution.unwrap().total())
+        .sum()
+}
 
-    // dbg!(&machines);
-
-    fn get_fewest_presses(goal: &[Joltage], wiring: &[ButtonWiring]) -> usize {
-        // this is a bfs
-        let num_buttons = wiring.len();
-        let mut q = VecDeque::new();
-        let mut visited: HashSet<Vec<Joltage>> = HashSet::new();
-        q.push_back((0, vec![0; goal.len()]));
-
-        loop {
-            if let Some((num_presses, state)) = q.pop_front() {
-                // dbg!(q.len());
-                if state.iter().zip(goal).any(|(&cur, &goal)| cur > goal) {
-                    // dead end: one of the counters exceed the target so we'll never get there.
-                    // dbg!(&state);
-                    continue;
-                }
+#[test]
+fn test_d10_2() {
+    println!("d10_2={}", d10_2(include_str!("day10.txt")));
+}
 
-                // dbg!(button, &wiring);
-                // dbg!(button, &state);
-                if goal.iter().zip(&state).all(|(x, y)| *x == *y) {
-                    // stop here, we have reached the goal state.
-                    return num_presses;
-                } else {
-                    for i in 0..num_buttons {
-                        let mut new_state = state.clone();
-                        for jolt_idx in &wiring[i] {
-                            new_state[*jolt_idx] += 1;
-                        }
-                        if !visited.contains(&new_state) {
-                            visited.insert(new_state.clone());
-                            q.push_back((num_presses + 1, new_state));
-                        } else {
-                            // println!("visited {:?}", new_state);
-                        }
-                    }
-                }
-            } else {
-                panic!();
-            }
-        }
+#[test]
+fn test_d10_solutions() {
+    let mut machines = parse_machines(include_str!("day10.txt"));
+    machines.extend(parse_machines("[##] (0) {1,2}"));
+
+    let lights = configure_lights(&machines);
+    assert_eq!(lights[3], Err(LightsError::Unreachable));
+    let lights: Vec<MachineSolution> = lights.into_iter().flatten().collect();
+    assert_eq!(
+        lights
+            .iter()
+            .map(|solution| (solution.machine, solution.total()))
+            .collect::<Vec<_>>(),
+        [(0, 2), (1, 3), (2, 2)]
+    );
+    for solution in &lights {
+        assert!(solution.validate_lights(&machines));
+        assert_eq!(solution.sequence().len(), solution.total());
+        assert!(solution.presses.iter().all(|&count| count <= 1));
     }
 
-    let mut total_presses = 0;
-    for (_, b, j) in &machines {
-        let num_presses = get_fewest_presses(j, b);
-        // dbg!((&j, &num_presses));
-        total_presses += num_presses;
-        println!("{}", total_presses);
+    let joltages = configure_joltages(&machines);
+    assert!(joltages[3].is_none());
+    let joltages: Vec<MachineSolution> = joltages.into_iter().flatten().collect();
+    assert_eq!(
+        joltages
+            .iter()
+            .map(|solution| (solution.machine, solution.total()))
+            .collect::<Vec<_>>(),
+        [(0, 10), (1, 12), (2, 11)]
+    );
+    for solution in &joltages {
+        assert!(solution.validate_joltages(&machines));
     }
 
-    total_presses
+    // one press too many, or a solution for the wrong machine
+    let mut wrong = joltages[2].clone();
+    wrong.presses[0] += 1;
+    assert!(!wrong.validate_joltages(&machines));
+    let wrong = MachineSolution {
+        machine: 1,
+        ..lights[0].clone()
+    };
+    assert!(!wrong.validate_lights(&machines));
+    let wrong = MachineSolution {
+        machine: 4,
+        ..lights[0].clone()
+    };
+    assert!(!wrong.validate_lights(&machines));
+    assert_eq!(
+        MachineSolution {
+            machine: 0,
+            presses: vec![0, 2, 0, 1]
+        }
+        .sequence(),
+        [1, 1, 3]
+    );
 }
 
 #[test]
-fn test_d10_2() {
-    println!("d10_2={}", d10_2(include_str!("day10.txt")));
+fn test_d10_joltage_presses() {
+    let mut machines = parse_machines(include_str!("day10.txt"));
+    machines.extend(parse_machines(
+        "[..] (0) {1,2}
+[..] (0,1) {1,2}
+[...] (0,1) (1,2) (0,2) {1,1,1}",
+    ));
+    let presses: Vec<_> = machines
+        .iter()
+        .map(|machine| fewest_joltage_presses(&machine.joltages, &machine.buttons))
+        .collect();
+    assert_eq!(presses, [Some(10), Some(12), Some(11), None, None, None]);
+
+    // more buttons than counters: pairs of neighbours, and each counter alone
+    let mut buttons: Vec<ButtonWiring> = (0..5)
+        .map(|counter| BitSet::from_indices(6, [counter, counter + 1]))
+        .collect();
+    buttons.extend((0..6).map(|counter| BitSet::from_indices(6, [counter])));
+    assert_eq!(
+        fewest_joltage_presses(&[3, 3, 4, 4, 0, 7], &buttons),
+        Some(3 + 4 + 7)
+    );
+    assert_eq!(fewest_joltage_presses(&[0; 6], &buttons), Some(0));
+
+    // many free buttons with a wide range, where only pressing the one for
+    // both counters as often as possible keeps the total down
+    let machine = &parse_machines("[....] (2) (3) (2,3) (2) (3) (3) (3) {0,0,121,189}")[0];
+    let presses = joltage_presses(&machine.joltages, &machine.buttons).unwrap();
+    assert_eq!(presses.iter().sum::<usize>(), 189);
+    assert_eq!(presses[2], 121);
 }
@@ -0,0 +1,897 @@
+use std::{cmp::Reverse, collections::HashMap};
+
+/// A point on the integer grid, with `y` growing downwards.
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq, PartialOrd, Ord)]
+pub struct Point {
+    pub x: i64,
+    pub y: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolygonError {
+    /// A closed axis-aligned loop needs at least four vertices.
+    TooFewVertices,
+    /// The edge from vertex `index` to the next one is neither horizontal nor
+    /// vertical.
+    DiagonalEdge { index: usize },
+    /// Vertex `index` is the same as the next one.
+    ZeroLengthEdge { index: usize },
+    /// The edges starting at vertices `first` and `second` touch or overlap.
+    SelfIntersecting { first: usize, second: usize },
+}
+
+/// A simple polygon whose edges are all horizontal or vertical, given by its
+/// vertices in order. The last vertex connects back to the first.
+///
+/// The polygon is a closed region: points on its boundary are inside it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RectilinearPolygon {
+    vertices: Vec<Point>,
+}
+
+impl RectilinearPolygon {
+    pub fn new(vertices: Vec<Point>) -> Result<RectilinearPolygon, PolygonError> {
+        let n = vertices.len();
+        if n < 4 {
+            return Err(PolygonError::TooFewVertices);
+        }
+        let polygon = RectilinearPolygon { vertices };
+        let edges: Vec<(Point, Point)> = polygon.edges().collect();
+        for (index, (p, q)) in edges.iter().enumerate() {
+            if p == q {
+                return Err(PolygonError::ZeroLengthEdge { index });
+            }
+            if p.x != q.x && p.y != q.y {
+                return Err(PolygonError::DiagonalEdge { index });
+            }
+        }
+
+        for first in 0..n {
+            for second in first + 1..n {
+                let (a, b) = edges[first];
+                let (c, d) = edges[second];
+                let adjacent = second == first + 1 || (first == 0 && second == n - 1);
+                let touch = if adjacent {
+                    // consecutive edges share a vertex, they only overlap if
+                    // the loop turns back on itself
+                    let (u, v) = if second == first + 1 { (a, d) } else { (c, b) };
+                    let shared = if second == first + 1 { b } else { a };
+                    (u.x == shared.x
+                        && v.x == shared.x
+                        && (u.y - shared.y).signum() == (v.y - shared.y).signum())
+                        || (u.y == shared.y
+                            && v.y == shared.y
+                            && (u.x - shared.x).signum() == (v.x - shared.x).signum())
+                } else {
+                    a.x.min(b.x) <= c.x.max(d.x)
+                        && c.x.min(d.x) <= a.x.max(b.x)
+                        && a.y.min(b.y) <= c.y.max(d.y)
+                        && c.y.min(d.y) <= a.y.max(b.y)
+                };
+                if touch {
+                    return Err(PolygonError::SelfIntersecting { first, second });
+                }
+            }
+        }
+
+        Ok(polygon)
+    }
+
+    pub fn vertices(&self) -> &[Point] {
+        &self.vertices
+    }
+
+    /// Each edge as (start, end), starting with the one from the first vertex.
+    pub fn edges(&self) -> impl Iterator<Item = (Point, Point)> + '_ {
+        self.vertices
+            .iter()
+            .zip(self.vertices.iter().cycle().skip(1))
+            .map(|(p, q)| (*p, *q))
+    }
+
+    /// Top left and bottom right corners of the smallest rectangle containing
+    /// the polygon.
+    pub fn bounding_box(&self) -> (Point, Point) {
+        let min_x = self.vertices.iter().map(|p| p.x).min().unwrap();
+        let min_y = self.vertices.iter().map(|p| p.y).min().unwrap();
+        let max_x = self.vertices.iter().map(|p| p.x).max().unwrap();
+        let max_y = self.vertices.iter().map(|p| p.y).max().unwrap();
+        (Point { x: min_x, y: min_y }, Point { x: max_x, y: max_y })
+    }
+
+    pub fn perimeter(&self) -> i64 {
+        self.edges()
+            .map(|(p, q)| (p.x - q.x).abs() + (p.y - q.y).abs())
+            .sum()
+    }
+
+    /// Geometric area, with the edges running through the vertices.
+    pub fn area(&self) -> i64 {
+        let twice_area: i64 = self.edges().map(|(p, q)| p.x * q.y - q.x * p.y).sum();
+        twice_area.abs() / 2
+    }
+
+    /// Number of grid points inside the polygon or on its boundary, i.e. the
+    /// number of tiles it covers when vertices are tile centres. This is
+    /// Pick's theorem, with as many points on the boundary as its length.
+    pub fn tile_count(&self) -> i64 {
+        self.area() + self.perimeter() / 2 + 1
+    }
+
+    pub fn on_boundary(&self, point: Point) -> bool {
+        self.edges().any(|(p, q)| {
+            point.x >= p.x.min(q.x)
+                && point.x <= p.x.max(q.x)
+                && point.y >= p.y.min(q.y)
+                && point.y <= p.y.max(q.y)
+        })
+    }
+
+    pub fn contains(&self, point: Point) -> bool {
+        self.contains_doubled(2 * point.x, 2 * point.y)
+    }
+
+    /// `contains` for the point (x / 2, y / 2), so that points halfway between
+    /// grid points can be tested too.
+    ///
+    /// Points off the boundary are inside if the boundary winds around them.
+    /// The winding number adds up the vertical edges right of the point that
+    /// a horizontal line through it crosses, upward edges counting 1 and
+    /// downward ones -1. Each edge covers its low end but not its high end,
+    /// so a line through a vertex or along a horizontal edge counts the
+    /// boundary once where it crosses, and zero or two opposite times where
+    /// it only touches.
+    fn contains_doubled(&self, x: i64, y: i64) -> bool {
+        let mut winding = 0;
+        for (p, q) in self.edges() {
+            let (p, q) = (
+                Point {
+                    x: 2 * p.x,
+                    y: 2 * p.y,
+                },
+                Point {
+                    x: 2 * q.x,
+                    y: 2 * q.y,
+                },
+            );
+            let (low, high) = (p.y.min(q.y), p.y.max(q.y));
+            if x >= p.x.min(q.x) && x <= p.x.max(q.x) && y >= low && y <= high {
+                return true;
+            }
+            if p.x == q.x && p.x > x && low <= y && y < high {
+                winding += if q.y < p.y { 1 } else { -1 };
+            }
+        }
+        winding != 0
+    }
+
+    /// Whether the closed rectangle with opposite corners `a` and `b` lies
+    /// entirely inside the polygon.
+    pub fn contains_rectangle(&self, a: Point, b: Point) -> bool {
+        let (min, max) = (
+            Point {
+                x: a.x.min(b.x),
+                y: a.y.min(b.y),
+            },
+            Point {
+                x: a.x.max(b.x),
+                y: a.y.max(b.y),
+            },
+        );
+
+        if min.x == max.x || min.y == max.y {
+            // a segment (or a point): its ends and a point in between each
+            // vertex coordinate along it must be inside
+            let horizontal = min.y == max.y;
+            let (start, end) = if horizontal {
+                (min.x, max.x)
+            } else {
+                (min.y, max.y)
+            };
+            let mut stops: Vec<i64> = self
+                .vertices
+                .iter()
+                .map(|p| if horizontal { p.x } else { p.y })
+                .filter(|&c| start < c && c < end)
+                .chain([start, end])
+                .collect();
+            stops.sort();
+            stops.dedup();
+            let doubled_stops = stops
+                .iter()
+                .map(|c| 2 * c)
+                .chain(stops.windows(2).map(|w| w[0] + w[1]));
+            return doubled_stops.into_iter().all(|c| {
+                if horizontal {
+                    self.contains_doubled(c, 2 * min.y)
+                } else {
+                    self.contains_doubled(2 * min.x, c)
+                }
+            });
+        }
+
+        // if no edge goes through the inside of the rectangle, the inside is
+        // either all in the polygon or all out of it
+        let crossed = self.edges().any(|(p, q)| {
+            if p.x == q.x {
+                min.x < p.x && p.x < max.x && p.y.min(q.y) < max.y && p.y.max(q.y) > min.y
+            } else {
+                min.y < p.y && p.y < max.y && p.x.min(q.x) < max.x && p.x.max(q.x) > min.x
+            }
+        });
+        !crossed && self.contains_doubled(min.x + max.x, min.y + max.y)
+    }
+
+    /// The largest rectangle inside the polygon whose corners satisfy
+    /// `constraint`, by tile count. Ties go to the rectangle closest to the
+    /// top left.
+    pub fn largest_rectangle(&self, constraint: CornerConstraint) -> Rectangle {
+        let index = ContainmentIndex::new(self);
+        let mut best: Option<Rectangle> = None;
+        let mut consider = |candidate: Rectangle| {
+            if best.is_none_or(|best| candidate.rank() > best.rank()) {
+                best = Some(candidate);
+            }
+        };
+
+        match constraint {
+            CornerConstraint::Free => index.for_each_maximal_rectangle(&mut consider),
+            CornerConstraint::OneVertex => {
+                let (down, up) = (index.vertical_runs(1), index.vertical_runs(-1));
+                for vertex in &self.vertices {
+                    index.for_each_rectangle_from(*vertex, &down, &up, &mut consider);
+                }
+            }
+            CornerConstraint::OppositeVertices => {
+                for (i, a) in self.vertices.iter().enumerate() {
+                    for b in &self.vertices[i + 1..] {
+                        if index.contains_rectangle(*a, *b) {
+                            consider(Rectangle::new(*a, *b));
+                        }
+                    }
+                }
+            }
+        }
+
+        // every vertex is a rectangle of its own
+        best.unwrap()
+    }
+}
+
+/// A rectangle with corners on grid points, edges included.
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+pub struct Rectangle {
+    pub min: Point,
+    pub max: Point,
+}
+
+impl Rectangle {
+    pub fn new(a: Point, b: Point) -> Rectangle {
+        Rectangle {
+            min: Point {
+                x: a.x.min(b.x),
+                y: a.y.min(b.y),
+            },
+            max: Point {
+                x: a.x.max(b.x),
+                y: a.y.max(b.y),
+            },
+        }
+    }
+
+    /// Number of grid points in the rectangle, like
+    /// [`RectilinearPolygon::tile_count`].
+    pub fn tile_count(&self) -> i64 {
+        (self.max.x - self.min.x + 1) * (self.max.y - self.min.y + 1)
+    }
+
+    /// Orders rectangles by tile count, preferring the one closest to the top
+    /// left among equally large ones.
+    fn rank(&self) -> (i64, Reverse<(i64, i64, i64, i64)>) {
+        (
+            self.tile_count(),
+            Reverse((self.min.y, self.min.x, self.max.y, self.max.x)),
+        )
+    }
+}
+
+/// Which corners of a rectangle must be vertices of the polygon.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CornerConstraint {
+    /// Any corners.
+    Free,
+    /// At least one corner is a vertex.
+    OneVertex,
+    /// Two opposite corners are vertices.
+    OppositeVertices,
+}
+
+/// Answers containment queries for a [`RectilinearPolygon`] without looking
+/// at its edges again.
+///
+/// The sorted vertex coordinates split each axis into elementary cells: the
+/// coordinates themselves and the open intervals between them. No edge
+/// starts or ends inside a cell, so each cell of the resulting grid is either
+/// entirely inside the polygon or entirely out of it. A prefix sum over the
+/// outside cells then tells whether any rectangle contains one.
+#[derive(Debug, Clone)]
+pub struct ContainmentIndex {
+    xs: Vec<i64>,
+    ys: Vec<i64>,
+    x_cells: HashMap<i64, usize>,
+    y_cells: HashMap<i64, usize>,
+    /// `outside[j][i]` is the number of outside cells in rows `..j` and
+    /// columns `..i`.
+    outside: Vec<Vec<u32>>,
+}
+
+impl ContainmentIndex {
+    pub fn new(polygon: &RectilinearPolygon) -> ContainmentIndex {
+        let mut xs: Vec<i64> = polygon.vertices.iter().map(|p| p.x).collect();
+        let mut ys: Vec<i64> = polygon.vertices.iter().map(|p| p.y).collect();
+        xs.sort();
+        xs.dedup();
+        ys.sort();
+        ys.dedup();
+        let x_cells: HashMap<i64, usize> =
+            xs.iter().enumerate().map(|(k, &x)| (x, 2 * k)).collect();
+        let y_cells: HashMap<i64, usize> =
+            ys.iter().enumerate().map(|(k, &y)| (y, 2 * k)).collect();
+        let (width, height) = (2 * xs.len() - 1, 2 * ys.len() - 1);
+
+        // cells on the boundary, and the winding of the vertical edges
+        // through each cell, with the same half-open rule as
+        // `RectilinearPolygon::contains`
+        let mut boundary = vec![vec![false; width]; height];
+        let mut windings = vec![vec![0; width]; height];
+        for (p, q) in polygon.edges() {
+            let (i0, i1) = (x_cells[&p.x.min(q.x)], x_cells[&p.x.max(q.x)]);
+            let (j0, j1) = (y_cells[&p.y.min(q.y)], y_cells[&p.y.max(q.y)]);
+            for row in &mut boundary[j0..=j1] {
+                row[i0..=i1].fill(true);
+            }
+            if i0 == i1 {
+                for row in &mut windings[j0..j1] {
+                    row[i0] += if q.y < p.y { 1 } else { -1 };
+                }
+            }
+        }
+
+        let mut outside = vec![vec![0; width + 1]; height + 1];
+        for j in 0..height {
+            // the edges on the left wind around a cell exactly as much as
+            // the ones on the right, the other way round
+            let mut winding = 0;
+            let mut row_outside = 0;
+            for i in 0..width {
+                if !boundary[j][i] && winding == 0 {
+                    row_outside += 1;
+                }
+                outside[j + 1][i + 1] = outside[j][i + 1] + row_outside;
+                winding += windings[j][i];
+            }
+        }
+
+        ContainmentIndex {
+            xs,
+            ys,
+            x_cells,
+            y_cells,
+            outside,
+        }
+    }
+
+    /// The elementary cell holding `c`, if it is within the polygon's extent.
+    fn cell(coordinates: &[i64], cells: &HashMap<i64, usize>, c: i64) -> Option<usize> {
+        if let Some(&cell) = cells.get(&c) {
+            return Some(cell);
+        }
+        match coordinates.binary_search(&c) {
+            Ok(k) => Some(2 * k),
+            Err(k) if k == 0 || k == coordinates.len() => None,
+            Err(k) => Some(2 * k - 1),
+        }
+    }
+
+    pub fn contains(&self, point: Point) -> bool {
+        self.contains_rectangle(point, point)
+    }
+
+    /// Same as [`RectilinearPolygon::contains_rectangle`], in constant time
+    /// when the corners are on vertex coordinates and logarithmic time
+    /// otherwise.
+    pub fn contains_rectangle(&self, a: Point, b: Point) -> bool {
+        let cells = (
+            Self::cell(&self.xs, &self.x_cells, a.x.min(b.x)),
+            Self::cell(&self.xs, &self.x_cells, a.x.max(b.x)),
+            Self::cell(&self.ys, &self.y_cells, a.y.min(b.y)),
+            Self::cell(&self.ys, &self.y_cells, a.y.max(b.y)),
+        );
+        let (Some(i0), Some(i1), Some(j0), Some(j1)) = cells else {
+            return false;
+        };
+        let count = self.outside[j1 + 1][i1 + 1] + self.outside[j0][i0]
+            - self.outside[j0][i1 + 1]
+            - self.outside[j1 + 1][i0];
+        count == 0
+    }
+
+    fn cell_inside(&self, i: usize, j: usize) -> bool {
+        self.outside[j + 1][i + 1] + self.outside[j][i]
+            == self.outside[j][i + 1] + self.outside[j + 1][i]
+    }
+
+    /// Calls `f` with, among others, every rectangle inside the polygon that
+    /// can't grow in any direction.
+    ///
+    /// Works row by row with the usual largest rectangle in a histogram
+    /// stack, the bars being the inside cells above each cell of the row.
+    /// An inside cell between vertex coordinates always has inside cells on
+    /// its edges, so the rectangles found start and end on vertex
+    /// coordinates.
+    fn for_each_maximal_rectangle(&self, f: &mut impl FnMut(Rectangle)) {
+        let (width, height) = (2 * self.xs.len() - 1, 2 * self.ys.len() - 1);
+        let mut tops: Vec<Option<usize>> = vec![None; width];
+        for j in 0..height {
+            for (i, top) in tops.iter_mut().enumerate() {
+                *top = match (self.cell_inside(i, j), *top) {
+                    (false, _) => None,
+                    (true, None) => Some(j),
+                    (true, Some(top)) => Some(top),
+                };
+            }
+            if j % 2 == 1 {
+                continue;
+            }
+
+            let bottom = self.ys[j / 2];
+            let heights = tops
+                .iter()
+                .map(|top| top.map_or(0, |top| bottom - self.ys[top / 2] + 1));
+            // (first column, height) with increasing heights
+            let mut stack: Vec<(usize, i64)> = vec![];
+            for (i, h) in heights.chain([0]).enumerate() {
+                let mut start = i;
+                while let Some(&(first, bar)) = stack.last() {
+                    if bar < h {
+                        break;
+                    }
+                    stack.pop();
+                    f(Rectangle {
+                        min: Point {
+                            x: self.xs[first / 2],
+                            y: bottom - bar + 1,
+                        },
+                        max: Point {
+                            x: self.xs[(i - 1) / 2],
+                            y: bottom,
+                        },
+                    });
+                    start = first;
+                }
+                if h > 0 {
+                    stack.push((start, h));
+                }
+            }
+        }
+    }
+
+    /// `runs[j][i]` is the number of inside cells from cell `(i, j)` onwards,
+    /// going down if `dy` is 1 and up if it is -1.
+    fn vertical_runs(&self, dy: isize) -> Vec<Vec<usize>> {
+        let (width, height) = (2 * self.xs.len() - 1, 2 * self.ys.len() - 1);
+        let rows: Vec<usize> = if dy > 0 {
+            (0..height).rev().collect()
+        } else {
+            (0..height).collect()
+        };
+        // built from the far end, each row extending the runs of the last
+        let mut runs: Vec<Vec<usize>> = Vec::with_capacity(height);
+        for j in rows {
+            let row = (0..width)
+                .map(|i| match runs.last() {
+                    _ if !self.cell_inside(i, j) => 0,
+                    Some(previous) => 1 + previous[i],
+                    None => 1,
+                })
+                .collect();
+            runs.push(row);
+        }
+        if dy > 0 {
+            runs.reverse();
+        }
+        runs
+    }
+
+    /// Calls `f` with, among others, the largest rectangles inside the
+    /// polygon with a corner at `corner`, one per width and direction.
+    /// `down` and `up` are the `vertical_runs` in each direction.
+    fn for_each_rectangle_from(
+        &self,
+        corner: Point,
+        down: &[Vec<usize>],
+        up: &[Vec<usize>],
+        f: &mut impl FnMut(Rectangle),
+    ) {
+        let (Some(i0), Some(j0)) = (
+            Self::cell(&self.xs, &self.x_cells, corner.x),
+            Self::cell(&self.ys, &self.y_cells, corner.y),
+        ) else {
+            return;
+        };
+        let width = 2 * self.xs.len() - 1;
+        for (dx, dy, runs) in [(1, 1, down), (1, -1, up), (-1, 1, down), (-1, -1, up)] {
+            // rows reachable from the corner's row in every column so far,
+            // shrinking as the rectangle widens
+            let mut depth = usize::MAX;
+            let mut i = i0 as isize;
+            while (0..width as isize).contains(&i) {
+                let column = i as usize;
+                depth = depth.min(runs[j0][column]);
+                if depth == 0 {
+                    break;
+                }
+                if column.is_multiple_of(2) {
+                    let last = j0 as isize + dy * (depth as isize - 1);
+                    f(Rectangle::new(
+                        corner,
+                        Point {
+                            x: self.xs[column / 2],
+                            y: self.ys[last as usize / 2],
+                        },
+                    ));
+                }
+                i += dx;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn polygon(vertices: &[(i64, i64)]) -> Result<RectilinearPolygon, PolygonError> {
+    RectilinearPolygon::new(vertices.iter().map(|&(x, y)| Point { x, y }).collect())
+}
+
+/// The loop through the red tiles of the day 9 example.
+#[cfg(test)]
+pub(crate) fn example_floor() -> RectilinearPolygon {
+    polygon(&[
+        (7, 1),
+        (11, 1),
+        (11, 7),
+        (9, 7),
+        (9, 5),
+        (2, 5),
+        (2, 3),
+        (7, 3),
+    ])
+    .unwrap()
+}
+
+/// A U with arms two wide and a gap between them two wide and four deep.
+#[cfg(test)]
+fn u_shape() -> RectilinearPolygon {
+    polygon(&[
+        (0, 0),
+        (2, 0),
+        (2, 4),
+        (4, 4),
+        (4, 0),
+        (6, 0),
+        (6, 6),
+        (0, 6),
+    ])
+    .unwrap()
+}
+
+#[test]
+fn test_polygon_validation() {
+    assert_eq!(
+        polygon(&[(0, 0), (1, 0), (1, 1)]),
+        Err(PolygonError::TooFewVertices)
+    );
+    assert_eq!(
+        polygon(&[(0, 0), (2, 0), (2, 2), (1, 3)]),
+        Err(PolygonError::DiagonalEdge { index: 2 })
+    );
+    assert_eq!(
+        polygon(&[(0, 0), (2, 0), (2, 0), (2, 2), (0, 2)]),
+        Err(PolygonError::ZeroLengthEdge { index: 1 })
+    );
+    // a figure of eight
+    assert_eq!(
+        polygon(&[(0, 0), (2, 0), (2, 4), (4, 4), (4, 2), (0, 2)]),
+        Err(PolygonError::SelfIntersecting {
+            first: 1,
+            second: 4
+        })
+    );
+    // going back along the previous edge
+    assert_eq!(
+        polygon(&[(0, 0), (4, 0), (2, 0), (2, 2), (0, 2)]),
+        Err(PolygonError::SelfIntersecting {
+            first: 0,
+            second: 1
+        })
+    );
+    assert!(polygon(&[(0, 0), (2, 0), (4, 0), (4, 2), (0, 2)]).is_ok());
+}
+
+#[test]
+fn test_polygon_measures() {
+    let floor = example_floor();
+    assert_eq!(floor.perimeter(), 30);
+    assert_eq!(floor.area(), 30);
+    assert_eq!(floor.tile_count(), 46);
+    assert_eq!(
+        floor.bounding_box(),
+        (Point { x: 2, y: 1 }, Point { x: 11, y: 7 })
+    );
+
+    assert!(floor.contains(Point { x: 8, y: 2 }));
+    assert!(floor.contains(Point { x: 7, y: 1 }));
+    assert!(floor.contains(Point { x: 3, y: 4 }));
+    assert!(!floor.contains(Point { x: 8, y: 0 }));
+    assert!(!floor.contains(Point { x: 5, y: 2 }));
+    assert!(!floor.contains(Point { x: 8, y: 6 }));
+
+    assert!(floor.contains_rectangle(Point { x: 9, y: 5 }, Point { x: 2, y: 3 }));
+    assert!(floor.contains_rectangle(Point { x: 7, y: 1 }, Point { x: 11, y: 5 }));
+    assert!(!floor.contains_rectangle(Point { x: 7, y: 1 }, Point { x: 11, y: 7 }));
+    assert!(!floor.contains_rectangle(Point { x: 2, y: 5 }, Point { x: 11, y: 1 }));
+    assert!(floor.contains_rectangle(Point { x: 2, y: 5 }, Point { x: 11, y: 5 }));
+    assert!(!floor.contains_rectangle(Point { x: 2, y: 3 }, Point { x: 2, y: 1 }));
+}
+
+#[test]
+fn test_polygon_notch() {
+    // a U whose gap is exactly the rectangle between the tips of its arms:
+    // all four corners are on the boundary, but the rectangle is outside
+    let u = u_shape();
+    assert!(!u.contains_rectangle(Point { x: 2, y: 0 }, Point { x: 4, y: 4 }));
+    assert!(!u.contains_rectangle(Point { x: 2, y: 0 }, Point { x: 4, y: 0 }));
+    assert!(u.contains_rectangle(Point { x: 2, y: 4 }, Point { x: 4, y: 4 }));
+    assert!(u.contains_rectangle(Point { x: 0, y: 4 }, Point { x: 6, y: 6 }));
+    assert!(!u.contains_rectangle(Point { x: 0, y: 3 }, Point { x: 6, y: 6 }));
+}
+
+#[test]
+fn test_containment_index() {
+    let shapes = [
+        example_floor(),
+        u_shape(),
+        polygon(&[
+            (0, 0),
+            (1, 0),
+            (1, 2),
+            (2, 2),
+            (2, 0),
+            (3, 0),
+            (3, 3),
+            (0, 3),
+        ])
+        .unwrap(),
+    ];
+    for shape in &shapes {
+        let index = ContainmentIndex::new(shape);
+        let range = -1..13;
+        for (x0, y0, x1, y1) in range.clone().flat_map(|x0| {
+            let range = range.clone();
+            range.clone().flat_map(move |y0| {
+                let range = range.clone();
+                range
+                    .clone()
+                    .flat_map(move |x1| range.clone().map(move |y1| (x0, y0, x1, y1)))
+            })
+        }) {
+            let (a, b) = (Point { x: x0, y: y0 }, Point { x: x1, y: y1 });
+            assert_eq!(
+                index.contains_rectangle(a, b),
+                shape.contains_rectangle(a, b),
+                "{a:?} {b:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_largest_rectangle() {
+    let shapes = [
+        example_floor(),
+        u_shape(),
+        // a square with a notch in each side, whose largest rectangle has no
+        // vertex corner
+        polygon(&[
+            (0, 0),
+            (4, 0),
+            (4, 1),
+            (6, 1),
+            (6, 0),
+            (10, 0),
+            (10, 4),
+            (9, 4),
+            (9, 6),
+            (10, 6),
+            (10, 10),
+            (6, 10),
+            (6, 9),
+            (4, 9),
+            (4, 10),
+            (0, 10),
+            (0, 6),
+            (1, 6),
+            (1, 4),
+            (0, 4),
+        ])
+        .unwrap(),
+    ];
+    for shape in &shapes {
+        let vertex = |p: &Point| shape.vertices().contains(p);
+        let mut candidates = vec![];
+        for (x0, x1) in (0..12).flat_map(|x0| (x0..12).map(move |x1| (x0, x1))) {
+            for (y0, y1) in (0..12).flat_map(|y0| (y0..12).map(move |y1| (y0, y1))) {
+                let rectangle = Rectangle {
+                    min: Point { x: x0, y: y0 },
+                    max: Point { x: x1, y: y1 },
+                };
+                if shape.contains_rectangle(rectangle.min, rectangle.max) {
+                    candidates.push(rectangle);
+                }
+            }
+        }
+        let brute_force = |constraint: CornerConstraint| {
+            *candidates
+                .iter()
+                .filter(|r| {
+                    let (a, b) = (r.min, r.max);
+                    let (c, d) = (Point { x: a.x, y: b.y }, Point { x: b.x, y: a.y });
+                    match constraint {
+                        CornerConstraint::Free => true,
+                        CornerConstraint::OneVertex => [a, b, c, d].iter().any(vertex),
+                        CornerConstraint::OppositeVertices => {
+                            (vertex(&a) && vertex(&b)) || (vertex(&c) && vertex(&d))
+                        }
+                    }
+                })
+                .max_by_key(|r| r.rank())
+                .unwrap()
+        };
+        for constraint in [
+            CornerConstraint::Free,
+            CornerConstraint::OneVertex,
+            CornerConstraint::OppositeVertices,
+        ] {
+            assert_eq!(
+                shape.largest_rectangle(constraint),
+                brute_force(constraint),
+                "{constraint:?}"
+            );
+        }
+    }
+
+    assert_eq!(
+        shapes[2].largest_rectangle(CornerConstraint::Free),
+        Rectangle {
+            min: Point { x: 1, y: 1 },
+            max: Point { x: 9, y: 9 }
+        }
+    );
+    assert!(
+        shapes[2]
+            .largest_rectangle(CornerConstraint::OneVertex)
+            .tile_count()
+            < 81
+    );
+}
+
+/// Points of the doubled grid around `shape` that are inside it or on its
+/// boundary, found by flood filling the outside from the bounding box's
+/// margin.
+#[cfg(test)]
+fn flood_filled(shape: &RectilinearPolygon) -> std::collections::HashSet<(i64, i64)> {
+    use std::collections::HashSet;
+
+    let (min, max) = shape.bounding_box();
+    let (x_range, y_range) = (2 * min.x - 1..=2 * max.x + 1, 2 * min.y - 1..=2 * max.y + 1);
+    let mut boundary = HashSet::new();
+    for (p, q) in shape.edges() {
+        for x in 2 * p.x.min(q.x)..=2 * p.x.max(q.x) {
+            for y in 2 * p.y.min(q.y)..=2 * p.y.max(q.y) {
+                boundary.insert((x, y));
+            }
+        }
+    }
+
+    let mut outside = HashSet::from([(*x_range.start(), *y_range.start())]);
+    let mut stack = vec![(*x_range.start(), *y_range.start())];
+    while let Some((x, y)) = stack.pop() {
+        for next in [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)] {
+            if x_range.contains(&next.0)
+                && y_range.contains(&next.1)
+                && !boundary.contains(&next)
+                && outside.insert(next)
+            {
+                stack.push(next);
+            }
+        }
+    }
+
+    x_range
+        .flat_map(|x| y_range.clone().map(move |y| (x, y)))
+        .filter(|point| !outside.contains(point))
+        .collect()
+}
+
+#[test]
+fn test_polygon_containment_shapes() {
+    let shapes = [
+        // L
+        polygon(&[(0, 0), (2, 0), (2, 4), (5, 4), (5, 6), (0, 6)]).unwrap(),
+        // L with a vertex in the middle of an edge, going anticlockwise
+        polygon(&[(0, 0), (0, 6), (5, 6), (5, 4), (2, 4), (2, 2), (2, 0)]).unwrap(),
+        // U
+        u_shape(),
+        // spiral
+        polygon(&[
+            (0, 0),
+            (10, 0),
+            (10, 10),
+            (2, 10),
+            (2, 4),
+            (6, 4),
+            (6, 6),
+            (4, 6),
+            (4, 8),
+            (8, 8),
+            (8, 2),
+            (0, 2),
+        ])
+        .unwrap(),
+        // comb with teeth and gaps one tile wide, so that rays run along
+        // edges and through vertices
+        polygon(&[
+            (0, 0),
+            (1, 0),
+            (1, 3),
+            (2, 3),
+            (2, 0),
+            (3, 0),
+            (3, 3),
+            (4, 3),
+            (4, 0),
+            (5, 0),
+            (5, 4),
+            (0, 4),
+        ])
+        .unwrap(),
+    ];
+    for shape in &shapes {
+        let inside = flood_filled(shape);
+        let index = ContainmentIndex::new(shape);
+        let (min, max) = shape.bounding_box();
+        for x in 2 * min.x - 1..=2 * max.x + 1 {
+            for y in 2 * min.y - 1..=2 * max.y + 1 {
+                let expected = inside.contains(&(x, y));
+                assert_eq!(shape.contains_doubled(x, y), expected, "({x}, {y}) / 2");
+                if x % 2 == 0 && y % 2 == 0 {
+                    let point = Point { x: x / 2, y: y / 2 };
+                    assert_eq!(shape.contains(point), expected, "{point:?}");
+                    assert_eq!(index.contains(point), expected, "{point:?}");
+                }
+            }
+        }
+
+        let points = || {
+            (min.x - 1..=max.x + 1)
+                .flat_map(|x| (min.y - 1..=max.y + 1).map(move |y| Point { x, y }))
+        };
+        for a in points() {
+            for b in points().filter(|b| b.x >= a.x && b.y >= a.y) {
+                let expected = (2 * a.x..=2 * b.x)
+                    .all(|x| (2 * a.y..=2 * b.y).all(|y| inside.contains(&(x, y))));
+                assert_eq!(shape.contains_rectangle(a, b), expected, "{a:?} {b:?}");
+                assert_eq!(index.contains_rectangle(a, b), expected, "{a:?} {b:?}");
+            }
+        }
+    }
+}